@@ -40,8 +40,8 @@ struct AuthRun<'a> {
 }
 
 pub struct AuthAgent<'a> {
-    env: &'static Environment<Env>,
-    display: &'static Display,
+    env: Environment<Env>,
+    display: Display,
     obj_reg_id: gio::RegistrationId,
     authority: polkit::Authority,
     pk_registered: bool,
@@ -51,11 +51,7 @@ pub struct AuthAgent<'a> {
 }
 
 impl<'a> AuthAgent<'a> {
-    pub async fn new(
-        bus: &'a gio::DBusConnection,
-        env: &'static Environment<Env>,
-        display: &'static Display,
-    ) -> AuthAgent<'a> {
+    pub async fn new(bus: &'a gio::DBusConnection, env: Environment<Env>, display: Display) -> AuthAgent<'a> {
         // the Authority wrapper does not take a bus connection, but it uses bus_get just like we do
         let authority = polkit::Authority::async__future().await.unwrap();
         let (req_tx, req_rx) = mpsc::unbounded();
@@ -100,8 +96,8 @@ impl<'a> Runnable for AuthAgent<'a> {
         } else {
             // TODO: support canceling queued reqs (select here too)
             let req = this.req_rx.select_next_some().await;
-            let env = this.env;
-            let display = this.display;
+            let env = this.env.clone();
+            let display = this.display.clone();
             if this.cur_dialog.is_none() {
                 let session =
                     polkit_agent::Session::new(&convert_ident(req.identities.first().unwrap()).unwrap(), &req.cookie);
@@ -149,7 +145,7 @@ impl<'a> Runnable for AuthAgent<'a> {
                             IcedInstance::new(AuthDialog::new(state.clone()), env.clone(), display.clone(), output)
                                 .boxed_local()
                         }),
-                        this.env,
+                        &this.env,
                     )
                     .await,
                 });
@@ -263,6 +259,7 @@ enum Msg {
     InputChange(String),
     SubmitResponse,
     CancelResponse,
+    FocusInput(bool),
 }
 
 struct AuthDialog {
@@ -271,6 +268,7 @@ struct AuthDialog {
     input_val: String,
     cancel_btn: widget::button::State,
     submit_btn: widget::button::State,
+    evl: addeventlistener::State,
 }
 
 impl AuthDialog {
@@ -281,6 +279,7 @@ impl AuthDialog {
             input_val: "".to_string(),
             cancel_btn: Default::default(),
             submit_btn: Default::default(),
+            evl: Default::default(),
         }
     }
 }
@@ -305,11 +304,11 @@ impl IcedSurface for AuthDialog {
     fn view(&mut self) -> Element<Self::Message> {
         use iced_native::*;
 
-        let title = Text::new(self.st.req.message.clone())
-            .size(20)
+        let title = typography::text(self.st.req.message.clone(), TextSize::Title)
             .horizontal_alignment(alignment::Horizontal::Center);
 
-        let mut elems = Column::new().spacing(16).push(title);
+        let spacing = theme().metrics.spacing;
+        let mut elems = Column::new().spacing(spacing * 4).push(title);
 
         // We're only supposed to have one not-done prompt, but Rust doesn't know.
         // Enforce this by borrowing outside of the loop here.
@@ -319,28 +318,45 @@ impl IcedSurface for AuthDialog {
             match e {
                 DialogElement::Info(txt) => {
                     // TODO: style
-                    elems = elems.push(Text::new(txt.clone()).size(18));
+                    elems = elems.push(Accessible::new(
+                        typography::text(txt.clone(), TextSize::Large),
+                        Role::Label,
+                        txt.clone(),
+                    ));
                 }
                 DialogElement::Error(txt) => {
                     // TODO: style
-                    elems = elems.push(Text::new(txt.clone()).size(18));
+                    elems = elems.push(
+                        Accessible::new(typography::text(txt.clone(), TextSize::Large), Role::Label, txt.clone())
+                            .description("Error"),
+                    );
                 }
                 DialogElement::Prompt { txt, echo_on, done } => {
                     let mut row = Row::new()
                         .align_items(Alignment::Center)
-                        .spacing(8)
-                        .push(Text::new(txt.clone()).size(18));
+                        .spacing(spacing * 2)
+                        .push(typography::text(txt.clone(), TextSize::Large));
                     if !done {
                         if let Some(input) = input_stref.take() {
+                            let focused = input.is_focused();
                             let mut input = TextInput::new(input, "", &self.input_val, Msg::InputChange)
                                 .on_submit(Msg::SubmitResponse)
                                 .width(Length::Fill)
                                 .style(style::Dialog)
-                                .padding(4);
+                                .padding(spacing);
                             if !echo_on {
                                 input = input.password();
                             }
-                            row = row.push(input);
+                            // announced as e.g. "Password", with the password role when not echoing
+                            let role = if *echo_on { Role::Text } else { Role::PasswordText };
+                            row = row.push(
+                                Accessible::new(
+                                    Focusable::new(input).on_focus(Msg::FocusInput(true)),
+                                    role,
+                                    txt.trim().trim_end_matches(':'),
+                                )
+                                .focused(focused),
+                            );
                         }
                     }
                     elems = elems.push(row);
@@ -350,42 +366,55 @@ impl IcedSurface for AuthDialog {
 
         elems = elems.push(
             Row::new()
-                .spacing(16)
-                .push(
-                    Button::new(
-                        &mut self.cancel_btn,
-                        Text::new("Cancel")
-                            .size(18)
-                            .horizontal_alignment(alignment::Horizontal::Center),
+                .spacing(spacing * 4)
+                .push(Accessible::new(
+                    Focusable::new(
+                        Button::new(
+                            &mut self.cancel_btn,
+                            typography::text("Cancel", TextSize::Large)
+                                .horizontal_alignment(alignment::Horizontal::Center),
+                        )
+                        .on_press(Msg::CancelResponse)
+                        .width(Length::Fill)
+                        .style(style::Action(style::ActionType::Bad)),
                     )
-                    .on_press(Msg::CancelResponse)
-                    .width(Length::Fill)
-                    .style(style::Action(style::ActionType::Bad)),
-                )
-                .push(
-                    Button::new(
-                        &mut self.submit_btn,
-                        Text::new("OK")
-                            .size(18)
-                            .horizontal_alignment(alignment::Horizontal::Center),
+                    .on_focus(Msg::FocusInput(false)),
+                    Role::PushButton,
+                    "Cancel",
+                ))
+                .push(Accessible::new(
+                    Focusable::new(
+                        Button::new(
+                            &mut self.submit_btn,
+                            typography::text("OK", TextSize::Large).horizontal_alignment(alignment::Horizontal::Center),
+                        )
+                        .on_press(Msg::SubmitResponse)
+                        .width(Length::Fill)
+                        .style(style::Action(style::ActionType::Good)),
                     )
-                    .on_press(Msg::SubmitResponse)
-                    .width(Length::Fill)
-                    .style(style::Action(style::ActionType::Good)),
-                ),
+                    .on_focus(Msg::FocusInput(false)),
+                    Role::PushButton,
+                    "OK",
+                )),
         );
 
-        let dialog = Container::new(elems)
-            .style(style::Dialog)
-            .width(Length::Units(420))
-            .padding(16);
+        let dialog = Accessible::new(
+            Container::new(elems)
+                .style(style::Dialog)
+                .width(Length::Units(420))
+                .padding(spacing * 4),
+            Role::Dialog,
+            self.st.req.message.clone(),
+        );
 
-        Container::new(Column::new().push(dialog))
+        let container = Container::new(Column::new().push(dialog))
             .style(style::DarkBar)
             .width(Length::Fill)
             .height(Length::Fill)
             .center_x()
-            .center_y()
+            .center_y();
+        AddEventListener::new(&mut self.evl, container)
+            .on_key_press(keyboard::KeyCode::Escape, Msg::CancelResponse)
             .into()
     }
 
@@ -393,6 +422,10 @@ impl IcedSurface for AuthDialog {
         vec![]
     }
 
+    fn accessible_name(&self) -> String {
+        "Authentication Required".to_owned()
+    }
+
     async fn update(&mut self, message: Self::Message) {
         match message {
             Msg::InputChange(new_input) => self.input_val = new_input,
@@ -408,6 +441,10 @@ impl IcedSurface for AuthDialog {
                 self.st.session.response(&sent_val);
             }
             Msg::CancelResponse => self.st.session.cancel(),
+            // the text input is not focused by the focus ring, only (un)focused along with it
+            // so that Enter doesn't both submit the input and press the focused button
+            Msg::FocusInput(true) => self.input.focus(),
+            Msg::FocusInput(false) => self.input.unfocus(),
         }
     }
 
@@ -425,7 +462,7 @@ impl IcedSurface for AuthDialog {
     }
 }
 
-async fn main_(env: &'static Environment<Env>, display: &'static Display) {
+async fn main_(env: Environment<Env>, display: Display) {
     let system_bus = gio::bus_get_future(gio::BusType::System).await.unwrap();
 
     let mut pk_agent = AuthAgent::new(&system_bus, env, display).await;
@@ -0,0 +1,68 @@
+//! The shell's own D-Bus interface, for compositor keybindings and scripts, e.g.:
+//! gdbus call --session --dest technology.unrelenting.waysmoke.Shell
+//!   --object-path /technology/unrelenting/waysmoke/Shell --method technology.unrelenting.waysmoke.Shell.SwitchWindows false
+
+use futures::channel::mpsc;
+use std::{cell::RefCell, rc::Rc};
+
+static OBJ_PATH: &str = "/technology/unrelenting/waysmoke/Shell";
+
+/// The registered object. Registered once for the process, since it stays on the bus across compositor reconnects.
+/// Without a registration (e.g. another instance has the path) the shell works, just without the interface.
+pub struct ShellObject {
+    switch_tx: Rc<RefCell<Option<mpsc::UnboundedSender<bool>>>>,
+    _reg: Option<gio::RegistrationId>,
+}
+
+impl ShellObject {
+    /// A channel for SwitchWindows requests (the bool is whether to go backwards),
+    /// replacing the previous one, which went away with the previous connection
+    pub fn switch_requests(&self) -> mpsc::UnboundedReceiver<bool> {
+        let (tx, rx) = mpsc::unbounded();
+        self.switch_tx.replace(Some(tx));
+        rx
+    }
+}
+
+pub fn reg_object(bus: &gio::DBusConnection) -> ShellObject {
+    let intf = gio::DBusNodeInfo::for_xml(include_str!("technology.unrelenting.waysmoke.Shell.xml"))
+        .unwrap()
+        .lookup_interface("technology.unrelenting.waysmoke.Shell")
+        .unwrap();
+    let switch_tx = Rc::new(RefCell::new(None::<mpsc::UnboundedSender<bool>>));
+    // silly rust wrapper, gdbus won't move to a new thread
+    let handler_switch_tx = fragile::Fragile::new(switch_tx.clone());
+    let reg = bus
+        .register_object(
+            OBJ_PATH,
+            &intf,
+            move |_conn, _uniq, _path, _intf, meth, args, invo| {
+                match meth {
+                    "SwitchWindows" => {
+                        if let Some((backwards,)) = args.get::<(bool,)>() {
+                            // no receiver while reconnecting to the compositor
+                            if let Some(tx) = handler_switch_tx.get().borrow().as_ref() {
+                                if let Err(e) = tx.unbounded_send(backwards) {
+                                    if !e.is_disconnected() {
+                                        eprintln!("Could not pass on SwitchWindows: {:?}", e)
+                                    }
+                                }
+                            }
+                        } else {
+                            eprintln!("WTF"); // prevented by gdbus
+                        }
+                    }
+                    _ => eprintln!("WTF"), // prevented by gdbus
+                }
+                invo.return_value(None);
+            },
+            |_conn, _uniq, _path, _intf, _prop| {
+                use glib::ToVariant;
+                1337_i32.to_variant()
+            },
+            |_conn, _uniq, _path, _intf, _prop, _val| false,
+        )
+        .map_err(|e| eprintln!("Could not register the D-Bus object: {}", e))
+        .ok();
+    ShellObject { switch_tx, _reg: reg }
+}
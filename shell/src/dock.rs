@@ -1,33 +1,87 @@
 use crate::{style, svc, util::*};
 use futures::prelude::*;
 use iced_native::widget::*;
-use std::cell::Cell;
+use std::rc::Rc;
 use wstk::*;
 
 lazy_static::lazy_static! {
-    static ref UNKNOWN_ICON: wstk::ImageHandle =
+    pub static ref UNKNOWN_ICON: wstk::ImageHandle =
         icons::icon_from_path(apps::icon("application-x-executable"));
 }
 
 pub const ICON_SIZE: u16 = 48;
 pub const POPOVER_HEIGHT_MAX: u16 = 420;
 pub const TOPLEVELS_WIDTH: u16 = 290;
-pub const APP_PADDING: u16 = 4;
-pub const DOCK_PADDING: u16 = 4;
-pub const DOCK_GAP: u16 = 8;
+pub const TOPLEVEL_INDENT: u16 = 16;
+pub const PREVIEW_WIDTH: u16 = 96;
+/// How often window previews are captured while a popover shows them
+pub const PREVIEW_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
 pub const BAR_HEIGHT: u16 = 10;
-pub const DOCK_HEIGHT: u16 = ICON_SIZE + APP_PADDING * 2 + DOCK_PADDING * 2;
-pub const DOCK_AND_GAP_HEIGHT: u16 = DOCK_HEIGHT + DOCK_GAP;
+pub const HANDLE_HEIGHT: u16 = 4;
+/// The strip at the bottom of the bar that shows the hidden dock when pointed at
+pub const HOVER_ZONE_HEIGHT: u16 = 2;
+
+/// Around an app's icon, from the theme's spacing like the other gaps
+pub fn app_padding() -> u16 {
+    theme().metrics.spacing
+}
+
+/// Around and between the docklets
+pub fn dock_padding() -> u16 {
+    theme().metrics.spacing
+}
+
+/// Between the dock and the bar
+pub fn dock_gap() -> u16 {
+    theme().metrics.spacing * 2
+}
+
+pub fn dock_height() -> u16 {
+    ICON_SIZE + app_padding() * 2 + dock_padding() * 2
+}
+
+pub fn dock_and_gap_height() -> u16 {
+    dock_height() + dock_gap()
+}
+
+/// For dragging apps around within the dock
+pub const APP_MIME_TYPE: &str = "application/x-waysmoke-dock-app";
+
+/// Which windows a dock shows and activates from its app icons
+#[derive(Clone)]
+pub enum DockMode {
+    /// Every window, no matter which monitor it's on
+    AllOutputs,
+    /// Only windows on the dock's own monitor, so that using a dock doesn't move focus to another one
+    OwnOutput(wl_output::WlOutput),
+}
+
+impl DockMode {
+    /// Whether the dock shows the toplevel, see `ToplevelState::is_on_output`
+    pub fn shows(&self, topl: &wstk::toplevels::ToplevelState) -> bool {
+        match self {
+            DockMode::AllOutputs => true,
+            DockMode::OwnOutput(output) => topl.is_on_output(output),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum DockletMsg {
     Hover,
+    TogglePinned,
+    /// An app was dropped onto this one, to be placed before it
+    MovePinned(String),
+    /// This docklet was dragged out of the dock and dropped nowhere
+    DraggedOut,
     App(app::Msg),
+    Workspace(workspaces::Msg),
 }
 
 #[derive(Debug, Clone)]
 pub enum Msg {
     IdxMsg(usize, DockletMsg),
+    Dismiss,
 }
 
 #[async_trait(?Send)]
@@ -41,38 +95,44 @@ pub trait Docklet {
         None
     }
     fn update(&mut self, msg: DockletMsg);
+    /// Called before `run` with whether the docklet's popover is currently shown
+    fn set_popover_shown(&mut self, _shown: bool) {}
+    /// Called when the dock hides, so that open menus don't come back with it
+    fn close_menus(&mut self) {}
     async fn run(&mut self);
 }
 
 mod app;
 mod power;
+mod workspaces;
 
-fn popover<'a>(icon_offset: i16, content: Element<'a, Msg>, popover_region: &'a Cell<Rectangle>) -> Element<'a, Msg> {
+fn popover(icon_offset: i16, content: Element<Msg>) -> Element<Msg> {
     use iced_graphics::{
         triangle::{Mesh2D, Vertex2D},
         Primitive,
     };
     use iced_native::*;
 
+    let background = theme().palette.background;
     let content_box = Container::new(content)
-        .style(style::Dock(style::DARK_COLOR))
+        .style(style::Dock(background))
         .width(Length::Shrink)
-        .padding(DOCK_PADDING);
+        .padding(dock_padding());
 
     let triangle = prim::Prim::new(Primitive::Mesh2D {
         buffers: Mesh2D {
             vertices: vec![
                 Vertex2D {
                     position: [0.0, 0.0],
-                    color: style::DARK_COLOR.into_linear(),
+                    color: background.into_linear(),
                 },
                 Vertex2D {
                     position: [8.0, 8.0],
-                    color: style::DARK_COLOR.into_linear(),
+                    color: background.into_linear(),
                 },
                 Vertex2D {
                     position: [16.0, 0.0],
-                    color: style::DARK_COLOR.into_linear(),
+                    color: background.into_linear(),
                 },
             ],
             indices: vec![0, 1, 2],
@@ -82,8 +142,7 @@ fn popover<'a>(icon_offset: i16, content: Element<'a, Msg>, popover_region: &'a
     .width(Length::Units(16))
     .height(Length::Units(8));
 
-    let content_col = GetRegion::new(
-        popover_region,
+    let content_col = InputRegion::new(
         Column::new()
             .align_items(Alignment::Center)
             .push(content_box)
@@ -107,29 +166,41 @@ fn popover<'a>(icon_offset: i16, content: Element<'a, Msg>, popover_region: &'a
 }
 
 pub struct Dock {
-    services: &'static svc::Services,
+    services: Rc<svc::Services>,
+    mode: DockMode,
     is_pointed: bool,
     is_touched: bool,
+    has_keyboard: bool,
+    evl: addeventlistener::State,
     hovered_docklet: Option<usize>,
 
-    dock_region: Cell<Rectangle>,
-    popover_region: Cell<Rectangle>,
-
     apps: Vec<app::AppDocklet>,
+    workspaces: workspaces::WorkspacesDocklet,
     power: power::PowerDocklet,
 }
 
 impl Dock {
-    pub fn new(services: &'static svc::Services) -> Dock {
-        let power = power::PowerDocklet::new(services);
+    pub fn new(services: Rc<svc::Services>, mode: DockMode) -> Dock {
+        let workspaces = workspaces::WorkspacesDocklet::new(services.clone(), mode.clone());
+        let power = power::PowerDocklet::new(&services);
+        if let DockMode::OwnOutput(output) = &mode {
+            // the exclusive zone, for cropping window previews captured from the output
+            let insets = wstk::capture::Insets {
+                bottom: BAR_HEIGHT as _,
+                ..Default::default()
+            };
+            services.capture.reserve(output, insets);
+        }
         Dock {
             services,
+            mode,
             is_pointed: false,
             is_touched: false,
+            has_keyboard: false,
+            evl: Default::default(),
             hovered_docklet: None,
-            dock_region: Default::default(),
-            popover_region: Default::default(),
             apps: Vec::new(),
+            workspaces,
             power,
         }
     }
@@ -137,30 +208,27 @@ impl Dock {
     fn update_apps(&mut self) {
         self.hovered_docklet = None;
 
-        let toplevels = self.services.toplevels.state();
-
-        let docked = vec![
-            "firefox",
-            "Alacritty",
-            "org.gnome.Lollypop",
-            "org.gnome.Nautilus",
-            "telegramdesktop",
-        ]; // TODO: GSettings
+        let services = self.services.clone();
+        let toplevels = services.toplevels.state();
+        let docked = services.pins.state();
 
         for id in docked.iter() {
             if self.apps.iter().find(|a| a.id() == *id).is_none() {
-                if let Some(app) = app::AppDocklet::from_id(self.services, id) {
+                if let Some(app) = app::AppDocklet::from_id(&self.services, &self.mode, id) {
                     self.apps.push(app);
                 }
             }
         }
 
-        for topl in toplevels.values() {
+        for topl in wstk::toplevels::sorted_toplevels(&toplevels, wstk::toplevels::ToplevelOrder::Creation)
+            .into_iter()
+            .filter(|t| self.mode.shows(t))
+        {
             if self.apps.iter().find(|a| topl.matches_id(a.id())).is_none() {
-                if let Some(app) = app::AppDocklet::from_id(self.services, &topl.app_id).or_else(|| {
+                if let Some(app) = app::AppDocklet::from_id(&self.services, &self.mode, &topl.app_id).or_else(|| {
                     topl.gtk_app_id
                         .as_ref()
-                        .and_then(|gid| app::AppDocklet::from_id(self.services, &gid))
+                        .and_then(|gid| app::AppDocklet::from_id(&self.services, &self.mode, &gid))
                 }) {
                     self.apps.push(app);
                 }
@@ -168,37 +236,64 @@ impl Dock {
         }
 
         self.apps.retain(|a| {
-            docked.iter().any(|id| a.id() == *id) || toplevels.values().any(|topl| topl.matches_id(a.id()))
+            docked.iter().any(|id| a.id() == *id)
+                || toplevels
+                    .values()
+                    .any(|topl| self.mode.shows(topl) && topl.matches_id(a.id()))
         });
+
+        // pinned apps in the pinned order, then the rest in the order they were opened
+        self.apps
+            .sort_by_key(|a| docked.iter().position(|id| a.id() == *id).unwrap_or(usize::MAX));
+
+        for app in self.apps.iter_mut() {
+            app.pinned = docked.iter().any(|id| app.id() == *id);
+        }
     }
 
     fn docklets(&self) -> impl Iterator<Item = &dyn Docklet> {
+        let workspaces_shown = self.workspaces.is_shown();
         self.apps
             .iter()
             .map(|x| &*x as &dyn Docklet)
+            .chain(Some(&self.workspaces as &dyn Docklet).filter(move |_| workspaces_shown))
             .chain(std::iter::once(&self.power as &dyn Docklet))
     }
 
     fn docklets_mut(&mut self) -> impl Iterator<Item = &mut dyn Docklet> {
+        let workspaces_shown = self.workspaces.is_shown();
         self.apps
             .iter_mut()
             .map(|x| &mut *x as &mut dyn Docklet)
+            .chain(Some(&mut self.workspaces as &mut dyn Docklet).filter(move |_| workspaces_shown))
             .chain(std::iter::once(&mut self.power as &mut dyn Docklet))
     }
 
     fn width(&self) -> u16 {
         let (wid, cnt) = self.docklets().fold((0, 0), |(w, c), d| (w + d.width(), c + 1));
-        wid + DOCK_PADDING * (std::cmp::max(cnt as u16, 1) - 1) + DOCK_PADDING * 2
+        wid + dock_padding() * (std::cmp::max(cnt as u16, 1) - 1) + dock_padding() * 2
     }
 
     fn center_of_docklet(&self, id: usize) -> u16 {
-        DOCK_PADDING
-            + self.docklets().take(id).fold(0, |x, d| x + d.width() + DOCK_PADDING)
+        dock_padding()
+            + self.docklets().take(id).fold(0, |x, d| x + d.width() + dock_padding())
             + self.docklets().nth(id).unwrap().width() / 2
     }
 
+    fn is_shown(&self) -> bool {
+        self.is_pointed || self.is_touched || self.has_keyboard
+    }
+
+    fn close_menus_if_hidden(&mut self) {
+        if !self.is_shown() {
+            for docklet in self.docklets_mut() {
+                docklet.close_menus();
+            }
+        }
+    }
+
     fn hovered_docklet(&self) -> Option<usize> {
-        if self.is_pointed || self.is_touched {
+        if self.is_shown() {
             self.hovered_docklet
         } else {
             None
@@ -210,7 +305,7 @@ impl DesktopSurface for Dock {
     fn setup_lsh(&self, layer_surface: &Main<layer_surface::ZwlrLayerSurfaceV1>) {
         layer_surface
             .set_anchor(layer_surface::Anchor::Left | layer_surface::Anchor::Right | layer_surface::Anchor::Bottom);
-        layer_surface.set_size(0, (BAR_HEIGHT + DOCK_AND_GAP_HEIGHT + POPOVER_HEIGHT_MAX) as _);
+        layer_surface.set_size(0, (BAR_HEIGHT + dock_and_gap_height() + POPOVER_HEIGHT_MAX) as _);
         layer_surface.set_exclusive_zone(BAR_HEIGHT as _);
     }
 }
@@ -234,19 +329,17 @@ impl IcedSurface for Dock {
                 col = col.push(popover(
                     (dock_width as i16 / 2 - our_center as i16) * 2, // XXX: why is the *2 needed?
                     i,
-                    &self.popover_region,
                 ));
                 has_popover = true;
             }
         }
         if !has_popover {
-            self.popover_region.set(Default::default()); // probably not the best way to clear input region but w/e
             col = col.push(Space::with_height(Length::Units(POPOVER_HEIGHT_MAX)));
         }
 
-        if self.is_pointed || self.is_touched {
+        if self.is_shown() {
             let row = self.docklets().enumerate().fold(
-                Row::new().align_items(Alignment::Center).spacing(DOCK_PADDING),
+                Row::new().align_items(Alignment::Center).spacing(dock_padding()),
                 |row, (i, docklet)| {
                     row.push(
                         unsafe { &mut *(docklet as *const dyn Docklet as *mut dyn Docklet) }
@@ -257,89 +350,142 @@ impl IcedSurface for Dock {
             );
             // TODO: show toplevels for unrecognized apps
 
-            let dock = Container::new(
-                GetRegion::new(
-                    &self.dock_region,
-                    Container::new(row)
-                        .style(style::Dock(style::DARK_COLOR))
-                        .width(Length::Shrink)
-                        .height(Length::Shrink)
-                        .center_x()
-                        .center_y()
-                        .padding(DOCK_PADDING),
-                )
-                .center_x()
-                .center_y(),
-            )
+            let dock = Container::new(InputRegion::new(Accessible::new(
+                Container::new(row)
+                    .style(style::Dock(theme().palette.background))
+                    .width(Length::Shrink)
+                    .height(Length::Shrink)
+                    .center_x()
+                    .center_y()
+                    .padding(dock_padding()),
+                Role::ToolBar,
+                "Dock",
+            )))
             .width(Length::Fill)
-            .height(Length::Units(DOCK_HEIGHT))
+            .height(Length::Units(dock_height()))
             .center_x();
 
-            col = col.push(dock).push(Space::with_height(Length::Units(DOCK_GAP)));
+            col = col.push(dock).push(Space::with_height(Length::Units(dock_gap())));
         } else {
-            col = col.push(Space::with_height(Length::Units(DOCK_AND_GAP_HEIGHT)));
+            col = col.push(Space::with_height(Length::Units(dock_and_gap_height())));
         }
 
-        let bar = Container::new(
-            prim::Prim::new(iced_graphics::Primitive::Quad {
-                bounds: iced_graphics::Rectangle::with_size(Size::new(192.0, 4.0)),
-                background: Background::Color(Color::WHITE),
-                border_radius: 2.0,
-                border_width: 0.0,
-                border_color: Color::WHITE,
-            })
-            .width(Length::Units(192))
-            .height(Length::Units(4)),
-        )
-        .style(style::DarkBar)
-        .width(Length::Fill)
-        .height(Length::Units(BAR_HEIGHT))
-        .center_x()
-        .center_y();
+        let handle = prim::Prim::new(iced_graphics::Primitive::Quad {
+            bounds: iced_graphics::Rectangle::with_size(Size::new(192.0, HANDLE_HEIGHT as _)),
+            background: Background::Color(theme().palette.foreground),
+            border_radius: 2.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        })
+        .width(Length::Units(192))
+        .height(Length::Units(HANDLE_HEIGHT));
+        let bar: Element<_> = if self.is_shown() {
+            InputRegion::new(
+                Container::new(handle)
+                    .style(style::DarkBar)
+                    .width(Length::Fill)
+                    .height(Length::Units(BAR_HEIGHT))
+                    .center_x()
+                    .center_y(),
+            )
+            .into()
+        } else {
+            // only the very edge shows the dock when pointed at
+            Container::new(
+                Column::new()
+                    .push(
+                        Container::new(handle)
+                            .width(Length::Fill)
+                            .height(Length::Units(BAR_HEIGHT - HOVER_ZONE_HEIGHT))
+                            .padding([(BAR_HEIGHT - HANDLE_HEIGHT) / 2, 0, 0, 0])
+                            .center_x(),
+                    )
+                    .push(InputRegion::new(Space::new(
+                        Length::Fill,
+                        Length::Units(HOVER_ZONE_HEIGHT),
+                    ))),
+            )
+            .style(style::DarkBar)
+            .width(Length::Fill)
+            .height(Length::Units(BAR_HEIGHT))
+            .into()
+        };
 
-        col.push(bar).into()
+        // Escape hides the dock and gives the keyboard back, see keyboard_interactivity
+        AddEventListener::new(&mut self.evl, col.push(bar))
+            .on_key_press(keyboard::KeyCode::Escape, Msg::Dismiss)
+            .into()
     }
 
-    fn input_region(&self, width: u32, _height: u32) -> Option<Vec<Rectangle<u32>>> {
-        fn pad(rect: Rectangle<u32>, n: u32) -> Rectangle<u32> {
-            if rect.x < n || rect.y < n {
-                return rect;
-            }
-            Rectangle {
-                x: rect.x - n,
-                y: rect.y - n,
-                width: rect.width + n * 2,
-                height: rect.height + n * 2,
-            }
-        }
-        let hover_zone_height = if self.is_pointed || self.is_touched {
-            BAR_HEIGHT
+    fn auto_input_region(&self) -> bool {
+        true
+    }
+
+    fn accessible_name(&self) -> String {
+        "Dock".to_owned()
+    }
+
+    fn keyboard_interactivity(&self) -> Option<layer_surface::KeyboardInteractivity> {
+        // take the keyboard when clicked (for Tab/arrow navigation), but only while shown
+        Some(if self.is_shown() {
+            layer_surface::KeyboardInteractivity::OnDemand
         } else {
-            2
-        };
-        let bar = Rectangle {
-            x: 0,
-            y: (DOCK_AND_GAP_HEIGHT + POPOVER_HEIGHT_MAX + BAR_HEIGHT - hover_zone_height) as _,
-            width,
-            height: hover_zone_height as _,
-        };
-        let mut result = vec![bar];
-        if self.is_pointed || self.is_touched {
-            result.push(pad(self.dock_region.get().snap(), 12));
-            if self.hovered_docklet().is_some() {
-                result.push(pad(self.popover_region.get().snap(), 6));
-            }
-        }
-        Some(result)
+            layer_surface::KeyboardInteractivity::None
+        })
     }
 
     fn retained_images(&mut self) -> Vec<wstk::ImageHandle> {
         self.docklets().flat_map(|d| d.retained_icon()).collect()
     }
 
+    fn after_draw(&mut self, surface: &wl_surface::WlSurface) {
+        // kept while the dock is hidden, so windows minimize to where their app shows up
+        for app in self.apps.iter_mut() {
+            app.report_minimize_rects(surface);
+        }
+    }
+
     async fn update(&mut self, message: Self::Message) {
         match message {
             Msg::IdxMsg(i, DockletMsg::Hover) => self.hovered_docklet = Some(i),
+            Msg::Dismiss => {
+                self.is_pointed = false;
+                self.has_keyboard = false;
+                self.hovered_docklet = None;
+                self.close_menus_if_hidden();
+            }
+            Msg::IdxMsg(i, DockletMsg::TogglePinned) => {
+                if let Some(app) = self.apps.get(i) {
+                    let id = app.id().to_owned();
+                    self.services.pins.update(|pinned| {
+                        if pinned.contains(&id) {
+                            pinned.retain(|p| *p != id);
+                        } else {
+                            pinned.push(id);
+                        }
+                    });
+                    self.update_apps();
+                }
+            }
+            Msg::IdxMsg(i, DockletMsg::MovePinned(id)) => {
+                if let Some(target) = self.apps.get(i).map(|a| a.id().to_owned()) {
+                    if target != id {
+                        self.services.pins.update(|pinned| {
+                            pinned.retain(|p| *p != id);
+                            let pos = pinned.iter().position(|p| *p == target).unwrap_or(pinned.len());
+                            pinned.insert(pos, id);
+                        });
+                        self.update_apps();
+                    }
+                }
+            }
+            Msg::IdxMsg(i, DockletMsg::DraggedOut) => {
+                if let Some(app) = self.apps.get(i) {
+                    let id = app.id().to_owned();
+                    self.services.pins.update(|pinned| pinned.retain(|p| *p != id));
+                    self.update_apps();
+                }
+            }
             Msg::IdxMsg(i, dmsg) => {
                 let docklet = self.docklets().nth(i).unwrap();
                 unsafe { &mut *(docklet as *const dyn Docklet as *mut dyn Docklet) }.update(dmsg)
@@ -348,16 +494,31 @@ impl IcedSurface for Dock {
     }
 
     async fn run(&mut self) -> Action {
+        let shown = self.hovered_docklet();
+        for (i, docklet) in self.docklets_mut().enumerate() {
+            docklet.set_popover_shown(shown == Some(i));
+        }
+        // the workspaces docklet isn't run while hidden, so it wouldn't notice workspaces showing up
+        let workspaces_hidden = !self.workspaces.is_shown();
         // ARGH: avoiding multiple mutable borrows is so hard in a situation like this!
         //       even sel's Drop (!) mutably borrows self.apps, hence the clone/drop dance.
         let sel = future::select(
-            self.services.toplevels.subscribe(),
+            future::select(
+                // pins can change from the dock of another output too
+                future::select(self.services.toplevels.subscribe(), self.services.pins.subscribe()),
+                MaybeFuture::new(Some(self.services.workspaces.subscribe()).filter(|_| workspaces_hidden)),
+            ),
             future::select_all(self.docklets_mut().map(|x| x.run())),
         )
         .await;
-        if let future::Either::Left(((), _)) = sel {
+        if let future::Either::Left((ref which, _)) = sel {
+            let apps_changed = matches!(which, future::Either::Left(_));
             drop(sel);
-            self.update_apps();
+            if apps_changed {
+                self.update_apps();
+            } else {
+                self.workspaces.update_items();
+            }
         }
         Action::Rerender
     }
@@ -368,7 +529,10 @@ impl IcedSurface for Dock {
 
     async fn on_pointer_leave(&mut self) {
         self.is_pointed = false;
-        self.hovered_docklet = None;
+        if !self.has_keyboard {
+            self.hovered_docklet = None;
+        }
+        self.close_menus_if_hidden();
     }
 
     async fn on_touch_enter(&mut self) {
@@ -377,5 +541,18 @@ impl IcedSurface for Dock {
 
     async fn on_touch_leave(&mut self) {
         self.is_touched = false;
+        self.close_menus_if_hidden();
+    }
+
+    async fn on_keyboard_enter(&mut self) {
+        self.has_keyboard = true;
+    }
+
+    async fn on_keyboard_leave(&mut self) {
+        self.has_keyboard = false;
+        if !self.is_pointed {
+            self.hovered_docklet = None;
+        }
+        self.close_menus_if_hidden();
     }
 }
@@ -4,7 +4,11 @@ use iced_native::{
     alignment::{Horizontal, Vertical},
     widget,
 };
-use std::cell::Ref;
+use std::{
+    cell::{Cell, Ref, RefCell},
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 
 lazy_static::lazy_static! {
     static ref PLAY_ICON: wstk::ImageHandle =
@@ -18,10 +22,24 @@ lazy_static::lazy_static! {
 #[derive(Debug, Clone)]
 pub enum Msg {
     ActivateApp,
-    ActivateToplevel(usize),
+    NewWindow,
+    Quit,
+    OpenUris(Vec<String>),
+    ActivateToplevel(wstk::toplevels::ToplevelId),
+    CloseToplevel(wstk::toplevels::ToplevelId),
     MediaControl(usize, &'static str),
 }
 
+/// Window thumbnails for the popover. Captured in a task of their own, since the docklet's `run`
+/// is cancelled on every input event and a capture can take a while.
+#[derive(Default)]
+struct Previews {
+    images: RefCell<HashMap<wstk::toplevels::ToplevelId, wstk::ImageHandle>>,
+    refreshed: Cell<Option<Instant>>,
+    capturing: Cell<bool>,
+    changed: wstk::event_listener::Event,
+}
+
 #[derive(Default)]
 struct MediaBtns {
     play: widget::button::State,
@@ -29,31 +47,51 @@ struct MediaBtns {
 }
 
 pub struct AppDocklet {
-    services: &'static svc::Services,
+    services: Rc<svc::Services>,
+    mode: DockMode,
     app: apps::App,
     icon: wstk::ImageHandle,
+    pub pinned: bool,
     button: widget::button::State,
+    tooltip: tooltip::State,
     evl: addeventlistener::State,
+    menu: contextmenu::State,
+    drag: dragsource::State,
     toplevels_scrollable: widget::scrollable::State,
-    toplevels_buttons: Vec<widget::button::State>,
+    toplevels_buttons: Vec<(widget::button::State, widget::button::State)>,
     media_buttons: Vec<MediaBtns>,
+    /// Where the docklet was last drawn on the dock surface
+    region: Cell<Rectangle>,
+    /// What was last sent to the compositor as each window's minimize target
+    minimize_rects: HashMap<wstk::toplevels::ToplevelId, Rectangle>,
+    previews: Rc<Previews>,
+    popover_shown: bool,
 }
 
 impl AppDocklet {
-    pub fn new(services: &'static svc::Services, app: apps::App) -> AppDocklet {
+    pub fn new(services: Rc<svc::Services>, mode: DockMode, app: apps::App) -> AppDocklet {
         let icon = app
             .icon()
             .map(icons::icon_from_path)
             .unwrap_or_else(|| UNKNOWN_ICON.clone());
         AppDocklet {
             services,
+            mode,
             app,
             icon,
+            pinned: false,
             button: Default::default(),
+            tooltip: Default::default(),
             evl: Default::default(),
+            menu: Default::default(),
+            drag: Default::default(),
             toplevels_scrollable: Default::default(),
             toplevels_buttons: Default::default(),
             media_buttons: Default::default(),
+            region: Default::default(),
+            minimize_rects: Default::default(),
+            previews: Default::default(),
+            popover_shown: false,
         }
     }
 
@@ -61,8 +99,78 @@ impl AppDocklet {
         &self.app.id
     }
 
-    pub fn from_id(services: &'static svc::Services, id: &str) -> Option<AppDocklet> {
-        apps::App::lookup(id).map(|a| AppDocklet::new(services, a))
+    pub fn from_id(services: &Rc<svc::Services>, mode: &DockMode, id: &str) -> Option<AppDocklet> {
+        apps::App::lookup(id).map(|a| AppDocklet::new(services.clone(), mode.clone(), a))
+    }
+
+    /// How long until the window previews should be captured again, None while they can't be seen
+    /// or are being captured
+    fn preview_refresh_delay(&self) -> Option<Duration> {
+        if !self.popover_shown || self.previews.capturing.get() {
+            return None;
+        }
+        Some(
+            self.previews
+                .refreshed
+                .get()
+                .map(|t| PREVIEW_REFRESH_INTERVAL.saturating_sub(t.elapsed()))
+                .unwrap_or(Duration::ZERO),
+        )
+    }
+
+    fn refresh_previews(&self) {
+        let ids = our_toplevels(&self.services.toplevels.state(), &self.mode, &self.app.id)
+            .map(|t| t.id)
+            .collect::<Vec<_>>();
+        let services = self.services.clone();
+        let previews = self.previews.clone();
+        previews.capturing.set(true);
+        glib::MainContext::default().spawn_local(async move {
+            // captured at twice the size to look sharp on HiDPI screens
+            let captured = future::join_all(
+                ids.iter()
+                    .map(|id| services.capture.capture_toplevel(*id, PREVIEW_WIDTH as u32 * 2)),
+            )
+            .await;
+            let mut images = previews.images.borrow_mut();
+            for (id, preview) in ids.iter().zip(captured) {
+                if let Some(preview) = preview {
+                    images.insert(*id, preview);
+                }
+            }
+            images.retain(|id, _| ids.contains(id));
+            previews.refreshed.set(Some(Instant::now()));
+            previews.capturing.set(false);
+            previews.changed.notify(usize::MAX);
+        });
+    }
+
+    fn launch(&self) {
+        if let Err(e) = self.app.info.launch(&[], None as Option<&gio::AppLaunchContext>) {
+            eprintln!("Could not launch {}: {:?}", self.app.id, e);
+        }
+    }
+
+    /// Tells the compositor that the app's windows minimize into this docklet,
+    /// for the ones that are new or when the docklet moved
+    pub fn report_minimize_rects(&mut self, surface: &wl_surface::WlSurface) {
+        let region = self.region.get();
+        let toplevels = self.services.toplevels.state();
+        let ours = our_toplevels(&toplevels, &self.mode, &self.app.id).collect::<Vec<_>>();
+        for topl in ours.iter() {
+            if self.minimize_rects.get(&topl.id) != Some(&region) {
+                self.services.toplevels.set_rectangle(
+                    topl,
+                    surface,
+                    region.x as _,
+                    region.y as _,
+                    region.width as _,
+                    region.height as _,
+                );
+                self.minimize_rects.insert(topl.id, region);
+            }
+        }
+        self.minimize_rects.retain(|id, _| ours.iter().any(|t| t.id == *id));
     }
 }
 
@@ -71,16 +179,28 @@ impl Docklet for AppDocklet {
     fn widget(&mut self) -> Element<DockletMsg> {
         use iced_native::*;
 
-        let running = our_toplevels(&self.services.toplevels.state(), self.id())
+        let running = our_toplevels(&self.services.toplevels.state(), &self.mode, self.id())
             .next()
             .is_some();
 
         let big_button = Button::new(&mut self.button, icons::icon_widget(self.icon.clone(), ICON_SIZE))
-            .style(style::Dock(style::DARK_COLOR))
-            .padding(APP_PADDING)
+            .style(style::Dock(theme().palette.background))
+            .padding(app_padding())
             .on_press(DockletMsg::App(Msg::ActivateApp));
+        let big_button = Tooltip::new(
+            &mut self.tooltip,
+            big_button,
+            self.app.info.name().to_string(),
+            tooltip::Position::Top,
+        )
+        .gap(dock_padding() + theme().metrics.spacing);
 
-        let mut content = Row::new().push(big_button);
+        // focusing from the keyboard shows the popover, like hovering
+        let mut content = Row::new().push(Accessible::new(
+            Focusable::new(big_button).on_focus(DockletMsg::Hover),
+            Role::PushButton,
+            self.app.info.name().to_string(),
+        ));
 
         let num_medias = our_medias(&self.services.media.state(), &self.app.id).count();
         while self.media_buttons.len() > num_medias {
@@ -93,8 +213,9 @@ impl Docklet for AppDocklet {
             .zip(self.media_buttons.iter_mut())
             .enumerate()
         {
-            content = content.push(
-                if media_data.status == svc::media::PlaybackStatus::Playing {
+            let playing = media_data.status == svc::media::PlaybackStatus::Playing;
+            let focusable = Focusable::new(
+                if playing {
                     Button::new(
                         &mut btns.pause,
                         Container::new(icons::icon_widget(PAUSE_ICON.clone(), ICON_SIZE / 2))
@@ -112,29 +233,68 @@ impl Docklet for AppDocklet {
                     .on_press(DockletMsg::App(Msg::MediaControl(i, "Play")))
                 }
                 .style(style::Toplevel)
-                .padding(APP_PADDING)
+                .padding(app_padding())
                 .height(Length::Fill),
             );
+            content = content.push(Accessible::new(
+                focusable,
+                Role::PushButton,
+                if playing { "Pause" } else { "Play" },
+            ));
         }
 
-        let listener = AddEventListener::new(&mut self.evl, content).on_pointer_enter(DockletMsg::Hover);
-
-        Container::new(listener)
-            .center_x()
-            .center_y()
-            .style(style::Dock(if running {
-                style::RUNNING_DARK_COLOR
+        let mut menu_items = vec![MenuItem::action(
+            if running { "New Window" } else { "Open" },
+            DockletMsg::App(Msg::NewWindow),
+        )];
+        menu_items.push(MenuItem::check("Keep in Dock", self.pinned, DockletMsg::TogglePinned));
+        if running {
+            menu_items.push(MenuItem::Separator);
+            menu_items.push(MenuItem::action("Quit", DockletMsg::App(Msg::Quit)));
+        }
+        let drag = DragSource::new(
+            &mut self.drag,
+            content,
+            DragData::new(APP_MIME_TYPE, self.app.id.clone()).icon(self.icon.clone(), ICON_SIZE),
+        )
+        .on_drag_end(|accepted| {
+            if accepted {
+                DockletMsg::Hover
+            } else {
+                DockletMsg::DraggedOut
+            }
+        });
+        let target = DropTarget::new(drag, |data| {
+            if data.mime_type == APP_MIME_TYPE {
+                DockletMsg::MovePinned(data.data)
             } else {
-                style::DARK_COLOR
-            }))
-            .into()
+                DockletMsg::App(Msg::OpenUris(data.uris()))
+            }
+        })
+        .mime_types(vec![APP_MIME_TYPE.to_owned(), dnd::MIME_URI_LIST.to_owned()]);
+        let menu = ContextMenu::new(&mut self.menu, target, menu_items);
+
+        let listener = AddEventListener::new(&mut self.evl, menu).on_pointer_enter(DockletMsg::Hover);
+
+        GetRegion::new(
+            &self.region,
+            Container::new(listener)
+                .center_x()
+                .center_y()
+                .style(style::Dock(if running {
+                    theme().palette.background_raised
+                } else {
+                    theme().palette.background
+                })),
+        )
+        .into()
     }
 
     fn width(&self) -> u16 {
         ICON_SIZE
-            + APP_PADDING * 2
+            + app_padding() * 2
             + our_medias(&self.services.media.state(), &self.app.id)
-                .fold(0, |acc, _| acc + ICON_SIZE / 2 + APP_PADDING * 2)
+                .fold(0, |acc, _| acc + ICON_SIZE / 2 + app_padding() * 2)
     }
 
     fn retained_icon(&self) -> Option<wstk::ImageHandle> {
@@ -144,31 +304,55 @@ impl Docklet for AppDocklet {
     fn popover(&mut self) -> Option<Element<DockletMsg>> {
         use iced_native::*;
 
-        while self.toplevels_buttons.len() < our_toplevels(&self.services.toplevels.state(), &self.app.id).count() {
+        let toplevels = self.services.toplevels.state();
+        let tree = our_toplevel_tree(&toplevels, &self.mode, &self.app.id);
+        let previews = self.previews.images.borrow();
+        while self.toplevels_buttons.len() < tree.len() {
             self.toplevels_buttons.push(Default::default());
         }
-        let mut btns = Scrollable::new(&mut self.toplevels_scrollable).spacing(2);
-        for (i, (topl, btn)) in our_toplevels(&self.services.toplevels.state(), &self.app.id)
-            .zip(self.toplevels_buttons.iter_mut())
-            .enumerate()
-        {
-            btns = btns.push(
-                Button::new(btn, Text::new(topl.title.clone()).size(14))
-                    .style(style::Toplevel)
-                    .width(Length::Fill)
-                    .on_press(DockletMsg::App(Msg::ActivateToplevel(i))),
-            )
+        let mut btns = Scrollable::new(&mut self.toplevels_scrollable).spacing(theme().metrics.spacing / 2);
+        for ((topl, depth), (btn, close_btn)) in tree.into_iter().zip(self.toplevels_buttons.iter_mut()) {
+            let mut label = Row::new()
+                .spacing(theme().metrics.spacing * 2)
+                .align_items(Alignment::Center);
+            if let Some(wstk::ImageHandle::Raster(preview)) = previews.get(&topl.id) {
+                label = label.push(widget::Image::new(preview.clone()).width(Length::Units(PREVIEW_WIDTH)));
+            }
+            label = label.push(typography::text(topl.title.clone(), TextSize::Small));
+            let close = Accessible::new(
+                Focusable::new(
+                    Button::new(close_btn, typography::text("×", TextSize::Small))
+                        .style(style::Toplevel)
+                        .on_press(DockletMsg::App(Msg::CloseToplevel(topl.id))),
+                ),
+                Role::PushButton,
+                "Close",
+            );
+            btns = btns.push(Accessible::new(
+                Row::new()
+                    .spacing(theme().metrics.spacing / 2)
+                    // dialogs are nested under their main window
+                    .push(Space::with_width(Length::Units(depth as u16 * TOPLEVEL_INDENT)))
+                    .push(Focusable::new(
+                        Button::new(btn, label)
+                            .style(style::Toplevel)
+                            .width(Length::Fill)
+                            .on_press(DockletMsg::App(Msg::ActivateToplevel(topl.id))),
+                    ))
+                    .push(close),
+                Role::ListItem,
+                topl.title.clone(),
+            ))
         }
-        let title = Text::new(self.app.info.name().to_string())
+        let title = typography::text(self.app.info.name().to_string(), TextSize::Body)
             .width(Length::Fill)
-            .horizontal_alignment(Horizontal::Center)
-            .size(16);
+            .horizontal_alignment(Horizontal::Center);
         Some(
             Column::new()
                 .width(Length::Units(TOPLEVELS_WIDTH))
                 .push(title)
-                .push(btns)
-                .spacing(DOCK_PADDING)
+                .push(Accessible::new(btns, Role::List, self.app.info.name().to_string()))
+                .spacing(dock_padding())
                 .into(),
         )
     }
@@ -176,21 +360,39 @@ impl Docklet for AppDocklet {
     fn update(&mut self, msg: DockletMsg) {
         match msg {
             DockletMsg::App(Msg::ActivateApp) => {
-                for topl in our_toplevels(&self.services.toplevels.state(), &self.app.id) {
-                    topl.handle.activate(&self.services.seat);
+                let toplevels = self.services.toplevels.state();
+                // clicking an app that's already in front minimizes it
+                if let Some(topl) = our_toplevels(&toplevels, &self.mode, &self.app.id).find(|t| t.state.activated) {
+                    self.services.toplevels.set_minimized(topl, true);
                     return;
                 }
-                self.app
-                    .info
-                    .launch(&[], None as Option<&gio::AppLaunchContext>)
-                    .unwrap()
+                if let Some(topl) = our_recent_toplevel(&toplevels, &self.mode, &self.app.id) {
+                    self.services.toplevels.activate(topl, &self.services.seat);
+                    return;
+                }
+                self.launch();
             }
-            DockletMsg::App(Msg::ActivateToplevel(topli)) => {
-                our_toplevels(&self.services.toplevels.state(), &self.app.id)
-                    .nth(topli)
-                    .unwrap()
-                    .handle
-                    .activate(&self.services.seat);
+            DockletMsg::App(Msg::NewWindow) => self.launch(),
+            DockletMsg::App(Msg::Quit) => {
+                // all of the app's windows, not only the ones shown in this dock
+                for topl in our_toplevels(&self.services.toplevels.state(), &DockMode::AllOutputs, &self.app.id) {
+                    self.services.toplevels.close(topl);
+                }
+            }
+            DockletMsg::App(Msg::OpenUris(uris)) => {
+                let uris = uris.iter().map(|u| u.as_str()).collect::<Vec<_>>();
+                if let Err(e) = self.app.info.launch_uris(&uris, None as Option<&gio::AppLaunchContext>) {
+                    eprintln!("Could not open {:?} with {}: {:?}", uris, self.app.id, e);
+                }
+            }
+            DockletMsg::App(Msg::ActivateToplevel(id)) => {
+                // the window might have closed since the popover was rendered
+                self.services.toplevels.activate_or_minimize(id, &self.services.seat)
+            }
+            DockletMsg::App(Msg::CloseToplevel(id)) => {
+                if let Some(topl) = self.services.toplevels.get(id) {
+                    self.services.toplevels.close(&topl);
+                }
             }
             DockletMsg::App(Msg::MediaControl(medi, op)) => {
                 self.services.media.control_player(
@@ -205,11 +407,22 @@ impl Docklet for AppDocklet {
         }
     }
 
+    fn set_popover_shown(&mut self, shown: bool) {
+        self.popover_shown = shown;
+    }
+
+    fn close_menus(&mut self) {
+        self.menu.close();
+    }
+
     async fn run(&mut self) {
         let this = self;
+        let mut refresh = MaybeFuture::new(this.preview_refresh_delay().map(|d| glib::timeout_future(d).fuse()));
         futures::select! {
             () = this.services.toplevels.subscribe().fuse() => (),
             () = this.services.media.subscribe().fuse() => (),
+            () = this.previews.changed.listen().fuse() => (),
+            () = refresh => this.refresh_previews(),
         };
     }
 }
@@ -217,10 +430,39 @@ impl Docklet for AppDocklet {
 // can't just have a method on self because rustc can't see through
 // the function boundary to know which parts of self are actually borrowed
 fn our_toplevels<'a>(
-    toplevels: &'a Ref<'a, wstk::toplevels::ToplevelStates>,
+    toplevels: &'a wstk::toplevels::ToplevelStates,
+    mode: &'a DockMode,
     id: &'a str,
 ) -> impl Iterator<Item = &'a wstk::toplevels::ToplevelState> {
-    toplevels.values().filter(move |topl| topl.matches_id(id))
+    our_toplevel_tree(toplevels, mode, id).into_iter().map(|(topl, _)| topl)
+}
+
+/// Main windows in the order they were opened followed by their dialogs, with the nesting depth
+fn our_toplevel_tree<'a>(
+    toplevels: &'a wstk::toplevels::ToplevelStates,
+    mode: &DockMode,
+    id: &str,
+) -> Vec<(&'a wstk::toplevels::ToplevelState, usize)> {
+    wstk::toplevels::toplevel_tree(toplevels, wstk::toplevels::ToplevelOrder::Creation, |topl| {
+        topl.matches_id(id) && mode.shows(topl)
+    })
+}
+
+/// The main window of the app's most recently used toplevel (the topmost one shown by the dock)
+fn our_recent_toplevel<'a>(
+    toplevels: &'a wstk::toplevels::ToplevelStates,
+    mode: &DockMode,
+    id: &str,
+) -> Option<&'a wstk::toplevels::ToplevelState> {
+    wstk::toplevels::sorted_toplevels(toplevels, wstk::toplevels::ToplevelOrder::RecentlyUsed)
+        .into_iter()
+        .find(|topl| topl.matches_id(id) && mode.shows(topl))
+        .map(|topl| {
+            wstk::toplevels::toplevel_ancestors(toplevels, topl)
+                .take_while(|t| mode.shows(t))
+                .last()
+                .unwrap_or(topl)
+        })
 }
 
 fn our_medias<'a>(
@@ -2,16 +2,20 @@ use crate::{dock::*, style, svc::power::*, util::apps};
 
 pub struct PowerDocklet {
     icon: wstk::ImageHandle,
+    label: String,
     evl: addeventlistener::State,
-    svc: &'static svc::power::PowerService,
+    tooltip: tooltip::State,
+    svc: Rc<svc::power::PowerService>,
 }
 
 impl PowerDocklet {
-    pub fn new(services: &'static svc::Services) -> Self {
+    pub fn new(services: &svc::Services) -> Self {
         PowerDocklet {
             icon: Self::the_icon(&services.power.state()),
+            label: Self::the_label(&services.power.state()),
             evl: Default::default(),
-            svc: &services.power,
+            tooltip: Default::default(),
+            svc: services.power.clone(),
         }
     }
 
@@ -21,6 +25,13 @@ impl PowerDocklet {
             _ => "ac-adapter",
         }))
     }
+
+    fn the_label(st: &svc::power::PowerState) -> String {
+        match st.total {
+            Some(PowerDeviceState::Battery { percentage, .. }) => format!("Battery: {:.0}%", percentage),
+            _ => "On AC power".to_owned(),
+        }
+    }
 }
 
 #[async_trait(?Send)]
@@ -28,18 +39,24 @@ impl Docklet for PowerDocklet {
     fn widget(&mut self) -> Element<DockletMsg> {
         let img = icons::icon_widget(self.icon.clone(), ICON_SIZE);
 
-        let listener = AddEventListener::new(&mut self.evl, img).on_pointer_enter(DockletMsg::Hover);
+        let tooltip = Tooltip::new(&mut self.tooltip, img, self.label.clone(), tooltip::Position::Top)
+            .gap(app_padding() + dock_padding() + theme().metrics.spacing);
+        let listener = AddEventListener::new(&mut self.evl, tooltip).on_pointer_enter(DockletMsg::Hover);
 
-        Container::new(listener)
-            .center_x()
-            .center_y()
-            .padding(APP_PADDING)
-            .style(style::Dock(style::DARK_COLOR))
-            .into()
+        Container::new(Accessible::new(
+            Focusable::new(listener).on_focus(DockletMsg::Hover),
+            Role::Label,
+            self.label.clone(),
+        ))
+        .center_x()
+        .center_y()
+        .padding(app_padding())
+        .style(style::Dock(theme().palette.background))
+        .into()
     }
 
     fn width(&self) -> u16 {
-        ICON_SIZE + APP_PADDING * 2
+        ICON_SIZE + app_padding() * 2
     }
 
     fn retained_icon(&self) -> Option<wstk::ImageHandle> {
@@ -52,5 +69,6 @@ impl Docklet for PowerDocklet {
         self.svc.subscribe().await;
         let st = self.svc.state();
         self.icon = Self::the_icon(&st);
+        self.label = Self::the_label(&st);
     }
 }
@@ -0,0 +1,186 @@
+use crate::{dock::*, style};
+use iced_native::mouse::ScrollDelta;
+use wstk::workspaces::{WorkspaceGroupId, WorkspaceId, WorkspaceStates};
+
+pub const WORKSPACE_WIDTH: u16 = 32;
+/// How far to scroll (in pixels, one wheel step is usually 10 or 15) to get to the next workspace
+pub const SCROLL_STEP: f32 = 10.0;
+
+#[derive(Debug, Clone)]
+pub enum Msg {
+    Activate(WorkspaceId),
+    Scroll(ScrollDelta),
+}
+
+struct Item {
+    id: WorkspaceId,
+    name: String,
+    active: bool,
+    urgent: bool,
+    button: button::State,
+}
+
+/// Buttons for the workspaces of the dock's output, scrolling over them goes to the next or previous one.
+/// Dropping windows onto them would need a protocol for moving windows between workspaces, which there isn't.
+pub struct WorkspacesDocklet {
+    services: Rc<svc::Services>,
+    mode: DockMode,
+    items: Vec<Item>,
+    scrolled: f32,
+    evl: addeventlistener::State,
+}
+
+impl WorkspacesDocklet {
+    pub fn new(services: Rc<svc::Services>, mode: DockMode) -> WorkspacesDocklet {
+        let mut docklet = WorkspacesDocklet {
+            services,
+            mode,
+            items: Vec::new(),
+            scrolled: 0.0,
+            evl: Default::default(),
+        };
+        docklet.update_items();
+        docklet
+    }
+
+    /// Nothing to show when the compositor doesn't have workspaces (or only one)
+    pub fn is_shown(&self) -> bool {
+        self.items.len() > 1
+    }
+
+    fn group(&self, states: &WorkspaceStates) -> Option<WorkspaceGroupId> {
+        match self.mode {
+            DockMode::AllOutputs => states.groups.keys().min().copied(),
+            DockMode::OwnOutput(ref output) => states.group_on_output(output).map(|g| g.id),
+        }
+    }
+
+    pub fn update_items(&mut self) {
+        let states = self.services.workspaces.state();
+        let workspaces = match self.group(&states) {
+            Some(group) => states.sorted_workspaces(group),
+            None => Vec::new(),
+        };
+        let mut old_items = std::mem::take(&mut self.items);
+        for (i, ws) in workspaces.into_iter().enumerate() {
+            let button = old_items
+                .iter()
+                .position(|item| item.id == ws.id)
+                .map(|pos| old_items.swap_remove(pos).button)
+                .unwrap_or_default();
+            self.items.push(Item {
+                id: ws.id,
+                name: if ws.name.is_empty() {
+                    (i + 1).to_string()
+                } else {
+                    ws.name.clone()
+                },
+                active: ws.state.active,
+                urgent: ws.state.urgent,
+                button,
+            });
+        }
+    }
+
+    fn activate(&self, id: WorkspaceId) {
+        if let Some(ws) = self.services.workspaces.get(id) {
+            if !ws.state.active {
+                self.services.workspaces.activate(&ws);
+            }
+        }
+    }
+
+    /// Goes to the workspace next to the active one, stopping at the ends
+    fn scroll(&mut self, delta: ScrollDelta) {
+        let (x, y) = match delta {
+            ScrollDelta::Lines { x, y } => (x * SCROLL_STEP, y * SCROLL_STEP),
+            ScrollDelta::Pixels { x, y } => (x, y),
+        };
+        // positive is up/left, towards the previous workspace
+        self.scrolled -= if y != 0.0 { y } else { x };
+        if self.scrolled.abs() < SCROLL_STEP {
+            return;
+        }
+        let forward = self.scrolled > 0.0;
+        self.scrolled = 0.0;
+        let active = match self.items.iter().position(|item| item.active) {
+            Some(i) => i,
+            None => return,
+        };
+        let target = if forward {
+            active + 1
+        } else {
+            match active.checked_sub(1) {
+                Some(i) => i,
+                None => return,
+            }
+        };
+        if let Some(item) = self.items.get(target) {
+            self.activate(item.id);
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Docklet for WorkspacesDocklet {
+    fn widget(&mut self) -> Element<DockletMsg> {
+        use iced_native::*;
+
+        let row = self
+            .items
+            .iter_mut()
+            .fold(Row::new().spacing(app_padding()), |row, item| {
+                let button = Button::new(
+                    &mut item.button,
+                    Text::new(item.name.clone())
+                        .width(Length::Fill)
+                        .height(Length::Fill)
+                        .horizontal_alignment(alignment::Horizontal::Center)
+                        .vertical_alignment(alignment::Vertical::Center),
+                )
+                .width(Length::Units(WORKSPACE_WIDTH))
+                .height(Length::Units(ICON_SIZE))
+                .style(style::Workspace {
+                    active: item.active,
+                    urgent: item.urgent,
+                })
+                .on_press(DockletMsg::Workspace(Msg::Activate(item.id)));
+                row.push(
+                    Accessible::new(
+                        Focusable::new(button).on_focus(DockletMsg::Hover),
+                        Role::ToggleButton,
+                        format!("Workspace {}", item.name),
+                    )
+                    .checked(item.active),
+                )
+            });
+        let listener = AddEventListener::new(&mut self.evl, row)
+            .on_pointer_enter(DockletMsg::Hover)
+            .on_scroll(|delta| DockletMsg::Workspace(Msg::Scroll(delta)));
+
+        Container::new(Accessible::new(listener, Role::List, "Workspaces"))
+            .center_x()
+            .center_y()
+            .padding(app_padding())
+            .style(style::Dock(theme().palette.background))
+            .into()
+    }
+
+    fn width(&self) -> u16 {
+        let count = self.items.len() as u16;
+        WORKSPACE_WIDTH * count + app_padding() * count.saturating_sub(1) + app_padding() * 2
+    }
+
+    fn update(&mut self, msg: DockletMsg) {
+        match msg {
+            DockletMsg::Workspace(Msg::Activate(id)) => self.activate(id),
+            DockletMsg::Workspace(Msg::Scroll(delta)) => self.scroll(delta),
+            _ => (),
+        }
+    }
+
+    async fn run(&mut self) {
+        self.services.workspaces.subscribe().await;
+        self.update_items();
+    }
+}
@@ -1,28 +1,69 @@
 use gio::prelude::ApplicationExt;
+use std::{cell::RefCell, rc::Rc};
 use wstk::*;
 
+mod dbus;
 mod dock;
 mod svc;
+mod switcher;
 mod util;
 
-async fn main_(env: &Environment<Env>, display: &Display) {
-    let app = gio::Application::new(
-        Some("technology.unrelenting.waysmoke.Shell"),
-        gio::ApplicationFlags::default(),
-    );
-    app.register(None as Option<&gio::Cancellable>).unwrap();
-    let session_bus = app.dbus_connection().unwrap();
+/// The parts of the shell that don't depend on the compositor, set up once and kept across reconnects
+struct Session {
+    _app: gio::Application,
+    shell_object: dbus::ShellObject,
+    power: Rc<svc::power::PowerService>,
+    media: Rc<svc::media::MediaService>,
+    pins: Rc<svc::pins::PinsService>,
+}
+
+thread_local! {
+    static SESSION: RefCell<Option<Rc<Session>>> = RefCell::new(None);
+}
 
-    let services: &'static _ = Box::leak(Box::new(svc::Services {
+impl Session {
+    async fn get() -> Rc<Session> {
+        if let Some(session) = SESSION.with(|s| s.borrow().clone()) {
+            return session;
+        }
+        let app = gio::Application::new(
+            Some("technology.unrelenting.waysmoke.Shell"),
+            gio::ApplicationFlags::default(),
+        );
+        app.register(None as Option<&gio::Cancellable>).unwrap();
+        let session_bus = app.dbus_connection().unwrap();
+        let session = Rc::new(Session {
+            shell_object: dbus::reg_object(&session_bus),
+            power: Rc::new(svc::power::PowerService::new(&session_bus).await),
+            media: Rc::new(svc::media::MediaService::new(&session_bus).await),
+            pins: Rc::new(svc::pins::PinsService::new()),
+            _app: app,
+        });
+        SESSION.with(|s| s.replace(Some(session.clone())));
+        session
+    }
+}
+
+async fn main_(env: Environment<Env>, display: Display) {
+    let session = Session::get().await;
+
+    let services = Rc::new(svc::Services {
         seat: env.get_all_seats()[0].detach(),
         toplevels: env.with_inner(|i| i.toplevel_service()),
-        power: svc::power::PowerService::new(&session_bus).await,
-        media: svc::media::MediaService::new(&session_bus).await,
-    }));
+        workspaces: env.with_inner(|i| i.workspace_service()),
+        power: session.power.clone(),
+        media: session.media.clone(),
+        pins: session.pins.clone(),
+        capture: wstk::capture::CaptureService::new(&env, &display),
+    });
+
+    let switch_rx = session.shell_object.switch_requests();
+    let mut switcher = switcher::SwitcherHost::new(services.clone(), &env, &display, switch_rx);
 
     let mut dock_mm = MultiMonitor::new(
         Box::new(|output, _output_info| {
-            IcedInstance::new(dock::Dock::new(services), env.clone(), display.clone(), output).boxed_local()
+            let dock = dock::Dock::new(services.clone(), dock::DockMode::OwnOutput(output.clone()));
+            IcedInstance::new(dock, env.clone(), display.clone(), output).boxed_local()
         }),
         &env,
     )
@@ -31,8 +72,9 @@ async fn main_(env: &Environment<Env>, display: &Display) {
     loop {
         futures::select! {
             _ = dock_mm.run().fuse() => (),
+            _ = switcher.run().fuse() => (),
         }
     }
 }
 
-wstk_main!(main_);
+wstk_main!(main_, OnDisconnect::Reconnect);
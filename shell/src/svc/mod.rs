@@ -1,9 +1,16 @@
 pub mod media;
+pub mod pins;
 pub mod power;
 
+use std::rc::Rc;
+
+/// Created for each compositor connection, sharing the bus services of the `Session`
 pub struct Services {
     pub seat: wstk::wl_seat::WlSeat,
-    pub toplevels: std::rc::Rc<wstk::toplevels::ToplevelService>,
-    pub power: power::PowerService,
-    pub media: media::MediaService,
+    pub toplevels: Rc<wstk::toplevels::ToplevelService>,
+    pub workspaces: Rc<wstk::workspaces::WorkspaceService>,
+    pub power: Rc<power::PowerService>,
+    pub media: Rc<media::MediaService>,
+    pub pins: Rc<pins::PinsService>,
+    pub capture: wstk::capture::CaptureService,
 }
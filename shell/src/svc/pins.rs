@@ -0,0 +1,85 @@
+use futures::prelude::*;
+use std::{
+    cell::{Ref, RefCell},
+    path::PathBuf,
+    rc::Rc,
+};
+use wstk::event_listener;
+
+const GROUP: &str = "dock";
+const KEY: &str = "pinned";
+
+/// Used until the user pins or unpins something
+const DEFAULT_PINNED: &[&str] = &[
+    "firefox",
+    "Alacritty",
+    "org.gnome.Lollypop",
+    "org.gnome.Nautilus",
+    "telegramdesktop",
+];
+
+/// The shell's settings file, next to the theme
+pub fn config_path() -> PathBuf {
+    glib::user_config_dir().join("waysmoke").join("shell.ini")
+}
+
+/// Apps kept in the dock (in order), shared by the docks of all outputs and saved in the config file
+pub struct PinsService {
+    notifier: Rc<event_listener::Event>,
+    state: Rc<RefCell<Vec<String>>>,
+}
+
+impl PinsService {
+    pub fn new() -> PinsService {
+        let kf = glib::KeyFile::new();
+        if let Err(e) = kf.load_from_file(config_path(), glib::KeyFileFlags::KEEP_COMMENTS) {
+            if !e.matches(glib::FileError::Noent) {
+                eprintln!("Could not load the shell config: {}", e);
+            }
+        }
+        let pinned = match kf.string_list(GROUP, KEY) {
+            Ok(list) => list.iter().map(|s| s.to_string()).collect(),
+            Err(_) => DEFAULT_PINNED.iter().map(|s| s.to_string()).collect(),
+        };
+        PinsService {
+            notifier: Rc::new(event_listener::Event::new()),
+            state: Rc::new(RefCell::new(pinned)),
+        }
+    }
+
+    pub fn state(&self) -> Ref<'_, Vec<String>> {
+        self.state.borrow()
+    }
+
+    pub fn subscribe(&self) -> impl Future<Output = ()> {
+        self.notifier.listen()
+    }
+
+    /// Changes the pinned apps, saves them and lets every dock know
+    pub fn update(&self, f: impl FnOnce(&mut Vec<String>)) {
+        f(&mut self.state.borrow_mut());
+        self.save();
+        self.notifier.notify(usize::MAX);
+    }
+
+    fn save(&self) {
+        // keeping whatever else is in the file
+        let path = config_path();
+        let kf = glib::KeyFile::new();
+        let _ = kf.load_from_file(&path, glib::KeyFileFlags::KEEP_COMMENTS);
+        kf.set_string_list(
+            GROUP,
+            KEY,
+            &self.state.borrow().iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+        );
+        let result = path
+            .parent()
+            .map(std::fs::create_dir_all)
+            .unwrap_or(Ok(()))
+            .map_err(|e| e.to_string())
+            .and_then(|()| kf.save_to_file(&path).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            eprintln!("Could not save the shell config: {}", e);
+        }
+    }
+}
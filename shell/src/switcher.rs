@@ -0,0 +1,304 @@
+use crate::{
+    dock::{dock_padding, UNKNOWN_ICON},
+    style, svc,
+    util::*,
+};
+use futures::{channel::mpsc, prelude::*};
+use iced_native::{keyboard, widget::*};
+use std::rc::Rc;
+use wstk::{
+    toplevels::{sorted_toplevels, toplevel_root, ToplevelId, ToplevelOrder},
+    *,
+};
+
+pub const ICON_SIZE: u16 = 64;
+pub const ITEM_WIDTH: u16 = 128;
+pub const ITEMS_PER_ROW: usize = 6;
+
+/// Around each window's icon and title
+pub fn item_padding() -> u16 {
+    theme().metrics.spacing * 2
+}
+
+#[derive(Debug, Clone)]
+pub enum Msg {
+    Modifiers(keyboard::Modifiers),
+    /// Tab, backwards with Shift
+    Tab,
+    Next(bool),
+    Activate(usize),
+    ActivateSelected,
+    Cancel,
+}
+
+struct Item {
+    key: ToplevelId,
+    title: String,
+    icon: wstk::ImageHandle,
+    button: button::State,
+}
+
+/// Lists the windows in most recently used order, releasing the modifiers (or Enter, or a click) activates one.
+/// Opened by `SwitcherHost` on request over D-Bus, which usually comes from an Alt+Tab keybinding,
+/// so the most recent window is the current one and the selection starts at the next one.
+pub struct Switcher {
+    services: Rc<svc::Services>,
+    requests: mpsc::UnboundedReceiver<bool>,
+    items: Vec<Item>,
+    selected: usize,
+    modifiers: keyboard::Modifiers,
+    done: bool,
+    evl: addeventlistener::State,
+}
+
+impl Switcher {
+    pub fn new(services: Rc<svc::Services>, backwards: bool, requests: mpsc::UnboundedReceiver<bool>) -> Switcher {
+        let mut switcher = Switcher {
+            services,
+            requests,
+            items: Vec::new(),
+            selected: 0,
+            modifiers: keyboard::Modifiers::empty(),
+            done: false,
+            evl: Default::default(),
+        };
+        switcher.update_items();
+        switcher.next(backwards);
+        switcher
+    }
+
+    /// Main windows in MRU order (a dialog being used counts for its main window).
+    /// Keeps the order of the windows already listed, so that it doesn't change while switching.
+    fn update_items(&mut self) {
+        let toplevels = self.services.toplevels.state();
+        let mut keys = Vec::<ToplevelId>::new();
+        for topl in sorted_toplevels(&toplevels, ToplevelOrder::RecentlyUsed) {
+            let key = toplevel_root(&toplevels, topl).id;
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        let selected_key = self.items.get(self.selected).map(|i| i.key);
+        self.items.retain(|i| keys.contains(&i.key));
+        for key in keys {
+            let topl = &toplevels[&key];
+            match self.items.iter_mut().find(|i| i.key == key) {
+                Some(item) => item.title = topl.title.clone(),
+                None => self.items.push(Item {
+                    key,
+                    title: topl.title.clone(),
+                    icon: apps::App::lookup(&topl.app_id)
+                        .or_else(|| topl.gtk_app_id.as_ref().and_then(|id| apps::App::lookup(id)))
+                        .and_then(|app| app.icon())
+                        .map(icons::icon_from_path)
+                        .unwrap_or_else(|| UNKNOWN_ICON.clone()),
+                    button: Default::default(),
+                }),
+            }
+        }
+        self.selected = selected_key
+            .and_then(|k| self.items.iter().position(|i| i.key == k))
+            .unwrap_or(0);
+    }
+
+    fn next(&mut self, backwards: bool) {
+        if self.items.is_empty() {
+            return;
+        }
+        self.selected = if backwards {
+            (self.selected + self.items.len() - 1) % self.items.len()
+        } else {
+            (self.selected + 1) % self.items.len()
+        };
+    }
+
+    fn activate(&mut self, i: usize) {
+        self.done = true;
+        let toplevels = self.services.toplevels.state();
+        if let Some(topl) = self.items.get(i).and_then(|item| toplevels.get(&item.key)) {
+            if topl.state.minimized {
+                self.services.toplevels.set_minimized(topl, false);
+            }
+            self.services.toplevels.activate(topl, &self.services.seat);
+        }
+    }
+}
+
+impl DesktopSurface for Switcher {
+    fn setup_lsh(&self, layer_surface: &Main<layer_surface::ZwlrLayerSurfaceV1>) {
+        layer_surface.set_anchor(
+            layer_surface::Anchor::Left
+                | layer_surface::Anchor::Top
+                | layer_surface::Anchor::Right
+                | layer_surface::Anchor::Bottom,
+        );
+        layer_surface.set_exclusive_zone(-1);
+        layer_surface.set_keyboard_interactivity(layer_surface::KeyboardInteractivity::Exclusive);
+    }
+}
+
+#[async_trait(?Send)]
+impl IcedSurface for Switcher {
+    type Message = Msg;
+
+    fn view(&mut self) -> Element<Self::Message> {
+        use iced_native::*;
+
+        let selected = self.selected;
+        let mut rows = Column::new().spacing(dock_padding());
+        let mut row = Row::new().spacing(dock_padding());
+        for (i, item) in self.items.iter_mut().enumerate() {
+            if i > 0 && i % ITEMS_PER_ROW == 0 {
+                rows = rows.push(row);
+                row = Row::new().spacing(dock_padding());
+            }
+            let content = Column::new()
+                .align_items(Alignment::Center)
+                .spacing(theme().metrics.spacing)
+                .push(icons::icon_widget(item.icon.clone(), ICON_SIZE))
+                .push(
+                    typography::text(item.title.clone(), TextSize::Small)
+                        .width(Length::Fill)
+                        .horizontal_alignment(alignment::Horizontal::Center),
+                );
+            let button = Button::new(&mut item.button, content)
+                .width(Length::Units(ITEM_WIDTH))
+                .padding(item_padding())
+                .style(style::Switcher(i == selected))
+                .on_press(Msg::Activate(i));
+            row = row.push(Accessible::new(button, Role::ListItem, item.title.clone()).focused(i == selected));
+        }
+        rows = rows.push(row);
+
+        let panel = InputRegion::new(Accessible::new(
+            Container::new(rows)
+                .style(style::Dock(theme().palette.background))
+                .padding(dock_padding() * 2),
+            Role::List,
+            "Windows",
+        ));
+        let container = Container::new(panel)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y();
+        AddEventListener::new(&mut self.evl, container)
+            .on_modifiers_change(Msg::Modifiers)
+            .on_key_press(keyboard::KeyCode::Tab, Msg::Tab)
+            .on_key_press(keyboard::KeyCode::Right, Msg::Next(false))
+            .on_key_press(keyboard::KeyCode::Left, Msg::Next(true))
+            .on_key_press(keyboard::KeyCode::Enter, Msg::ActivateSelected)
+            .on_key_press(keyboard::KeyCode::Escape, Msg::Cancel)
+            .into()
+    }
+
+    fn auto_input_region(&self) -> bool {
+        true
+    }
+
+    fn retained_images(&mut self) -> Vec<wstk::ImageHandle> {
+        self.items.iter().map(|i| i.icon.clone()).collect()
+    }
+
+    fn accessible_name(&self) -> String {
+        "Window Switcher".to_owned()
+    }
+
+    async fn update(&mut self, message: Self::Message) {
+        match message {
+            Msg::Modifiers(modifiers) => {
+                self.modifiers = modifiers;
+                // also when they were released before the switcher got the keyboard: a quick Alt+Tab
+                if !(modifiers.alt() || modifiers.control() || modifiers.logo()) {
+                    self.activate(self.selected);
+                }
+            }
+            Msg::Tab => self.next(self.modifiers.shift()),
+            Msg::Next(backwards) => self.next(backwards),
+            Msg::Activate(i) => self.activate(i),
+            Msg::ActivateSelected => self.activate(self.selected),
+            Msg::Cancel => self.done = true,
+        }
+    }
+
+    async fn run(&mut self) -> Action {
+        if self.done || self.items.is_empty() {
+            return Action::Close;
+        }
+        let this = self; // argh macro weirdness
+        futures::select! {
+            backwards = this.requests.select_next_some() => this.next(backwards),
+            () = this.services.toplevels.subscribe().fuse() => this.update_items(),
+        }
+        Action::Rerender
+    }
+
+    async fn on_keyboard_leave(&mut self) {
+        // the compositor took the keyboard away (e.g. for a different keybinding)
+        self.done = true;
+    }
+}
+
+/// Opens the switcher on requests (the bool is whether to go backwards) and runs it while it's open
+pub struct SwitcherHost<'a> {
+    services: Rc<svc::Services>,
+    env: &'a Environment<Env>,
+    display: &'a Display,
+    requests: mpsc::UnboundedReceiver<bool>,
+    open: Option<(IcedInstance<Switcher>, mpsc::UnboundedSender<bool>)>,
+}
+
+impl<'a> SwitcherHost<'a> {
+    pub fn new(
+        services: Rc<svc::Services>,
+        env: &'a Environment<Env>,
+        display: &'a Display,
+        requests: mpsc::UnboundedReceiver<bool>,
+    ) -> SwitcherHost<'a> {
+        SwitcherHost {
+            services,
+            env,
+            display,
+            requests,
+            open: None,
+        }
+    }
+
+    /// The output with the active window, where the user is probably looking
+    fn output(&self) -> Option<wl_output::WlOutput> {
+        let toplevels = self.services.toplevels.state();
+        toplevels
+            .values()
+            .find(|t| t.state.activated)
+            .and_then(|t| t.outputs.first().cloned())
+            .or_else(|| self.env.get_all_outputs().first().cloned())
+    }
+}
+
+#[async_trait(?Send)]
+impl<'a> Runnable for SwitcherHost<'a> {
+    async fn run(&mut self) -> bool {
+        let this = self; // argh macro weirdness
+        let mut run_open = MaybeFuture::new(this.open.as_mut().map(|(inst, _)| inst.run().fuse()));
+        futures::select! {
+            backwards = this.requests.select_next_some() => {
+                drop(run_open);
+                if let Some((_, ref tx)) = this.open {
+                    let _ = tx.unbounded_send(backwards);
+                } else if let Some(output) = this.output() {
+                    let (tx, rx) = mpsc::unbounded();
+                    let switcher = Switcher::new(this.services.clone(), backwards, rx);
+                    let inst = IcedInstance::new(switcher, this.env.clone(), this.display.clone(), output).await;
+                    this.open = Some((inst, tx));
+                }
+            },
+            cont = run_open => {
+                drop(run_open);
+                if !cont {
+                    this.open = None;
+                }
+            },
+        }
+        true
+    }
+}
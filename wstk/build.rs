@@ -0,0 +1,23 @@
+use std::{env, path::Path};
+use wayland_scanner::{generate_code, Side};
+
+/// Protocols that the wayland-protocols version used by SCTK doesn't have yet, see src/protocols.rs
+const PROTOCOLS: &[&str] = &[
+    "ext-foreign-toplevel-list-v1",
+    "ext-image-capture-source-v1",
+    "ext-image-copy-capture-v1",
+    "ext-workspace-v1",
+];
+
+fn main() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+    for name in PROTOCOLS {
+        let xml = format!("protocols/{}.xml", name);
+        println!("cargo:rerun-if-changed={}", xml);
+        generate_code(
+            &xml,
+            Path::new(&out_dir).join(format!("{}_client_api.rs", name)),
+            Side::Client,
+        );
+    }
+}
@@ -0,0 +1,1052 @@
+//! Exposes what the Accessible widgets (and menus) describe to screen readers over AT-SPI.
+//! Like the input region, the tree is collected while drawing: every surface is a window
+//! under the application root, and its nodes are numbered in drawing order.
+//! Actions and focus requests from assistive technologies are sent back to the surfaces.
+//! Set AT_SPI_BUS_ADDRESS to use a separate accessibility bus (e.g. for testing).
+
+use futures::channel::mpsc;
+use glib::{variant::ObjectPath, ToVariant};
+use iced_native::{Point, Rectangle};
+use std::{cell::RefCell, collections::HashMap};
+
+const ROOT_PATH: &str = "/org/a11y/atspi/accessible/root";
+const NULL_PATH: &str = "/org/a11y/atspi/null";
+const PATH_PREFIX: &str = "/org/a11y/atspi/accessible/";
+
+const INTERFACES: &str = r#"<node>
+  <interface name="org.a11y.atspi.Accessible">
+    <property name="Name" type="s" access="read"/>
+    <property name="Description" type="s" access="read"/>
+    <property name="Parent" type="(so)" access="read"/>
+    <property name="ChildCount" type="i" access="read"/>
+    <property name="Locale" type="s" access="read"/>
+    <property name="AccessibleId" type="s" access="read"/>
+    <method name="GetChildAtIndex">
+      <arg direction="in" name="index" type="i"/>
+      <arg direction="out" type="(so)"/>
+    </method>
+    <method name="GetChildren">
+      <arg direction="out" type="a(so)"/>
+    </method>
+    <method name="GetIndexInParent">
+      <arg direction="out" type="i"/>
+    </method>
+    <method name="GetRelationSet">
+      <arg direction="out" type="a(ua(so))"/>
+    </method>
+    <method name="GetRole">
+      <arg direction="out" type="u"/>
+    </method>
+    <method name="GetRoleName">
+      <arg direction="out" type="s"/>
+    </method>
+    <method name="GetLocalizedRoleName">
+      <arg direction="out" type="s"/>
+    </method>
+    <method name="GetState">
+      <arg direction="out" type="au"/>
+    </method>
+    <method name="GetAttributes">
+      <arg direction="out" type="a{ss}"/>
+    </method>
+    <method name="GetApplication">
+      <arg direction="out" type="(so)"/>
+    </method>
+    <method name="GetInterfaces">
+      <arg direction="out" type="as"/>
+    </method>
+  </interface>
+  <interface name="org.a11y.atspi.Application">
+    <property name="ToolkitName" type="s" access="read"/>
+    <property name="Version" type="s" access="read"/>
+    <property name="AtspiVersion" type="s" access="read"/>
+    <property name="Id" type="i" access="readwrite"/>
+    <method name="GetLocale">
+      <arg direction="in" name="lctype" type="u"/>
+      <arg direction="out" type="s"/>
+    </method>
+  </interface>
+  <interface name="org.a11y.atspi.Action">
+    <property name="NActions" type="i" access="read"/>
+    <method name="GetDescription">
+      <arg direction="in" name="index" type="i"/>
+      <arg direction="out" type="s"/>
+    </method>
+    <method name="GetName">
+      <arg direction="in" name="index" type="i"/>
+      <arg direction="out" type="s"/>
+    </method>
+    <method name="GetLocalizedName">
+      <arg direction="in" name="index" type="i"/>
+      <arg direction="out" type="s"/>
+    </method>
+    <method name="GetKeyBinding">
+      <arg direction="in" name="index" type="i"/>
+      <arg direction="out" type="s"/>
+    </method>
+    <method name="GetActions">
+      <arg direction="out" type="a(sss)"/>
+    </method>
+    <method name="DoAction">
+      <arg direction="in" name="index" type="i"/>
+      <arg direction="out" type="b"/>
+    </method>
+  </interface>
+  <interface name="org.a11y.atspi.Component">
+    <method name="Contains">
+      <arg direction="in" name="x" type="i"/>
+      <arg direction="in" name="y" type="i"/>
+      <arg direction="in" name="coord_type" type="u"/>
+      <arg direction="out" type="b"/>
+    </method>
+    <method name="GetExtents">
+      <arg direction="in" name="coord_type" type="u"/>
+      <arg direction="out" type="(iiii)"/>
+    </method>
+    <method name="GetPosition">
+      <arg direction="in" name="coord_type" type="u"/>
+      <arg direction="out" type="i"/>
+      <arg direction="out" type="i"/>
+    </method>
+    <method name="GetSize">
+      <arg direction="out" type="i"/>
+      <arg direction="out" type="i"/>
+    </method>
+    <method name="GrabFocus">
+      <arg direction="out" type="b"/>
+    </method>
+  </interface>
+</node>"#;
+
+/// A subset of the AT-SPI roles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Application,
+    Window,
+    Dialog,
+    Panel,
+    ToolBar,
+    Label,
+    Image,
+    PushButton,
+    ToggleButton,
+    CheckBox,
+    Text,
+    PasswordText,
+    List,
+    ListItem,
+    Menu,
+    MenuItem,
+    CheckMenuItem,
+    Separator,
+    ToolTip,
+    ProgressBar,
+}
+
+impl Role {
+    fn atspi(self) -> u32 {
+        match self {
+            Role::CheckBox => 7,
+            Role::CheckMenuItem => 8,
+            Role::Dialog => 16,
+            Role::Image => 27,
+            Role::Label => 29,
+            Role::List => 31,
+            Role::ListItem => 32,
+            Role::Menu => 33,
+            Role::MenuItem => 35,
+            Role::Panel => 39,
+            Role::PasswordText => 40,
+            Role::ProgressBar => 42,
+            Role::PushButton => 43,
+            Role::Separator => 50,
+            Role::Text => 61,
+            Role::ToggleButton => 62,
+            Role::ToolBar => 63,
+            Role::ToolTip => 64,
+            Role::Window => 69,
+            Role::Application => 75,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Role::CheckBox => "check box",
+            Role::CheckMenuItem => "check menu item",
+            Role::Dialog => "dialog",
+            Role::Image => "image",
+            Role::Label => "label",
+            Role::List => "list",
+            Role::ListItem => "list item",
+            Role::Menu => "menu",
+            Role::MenuItem => "menu item",
+            Role::Panel => "panel",
+            Role::PasswordText => "password text",
+            Role::ProgressBar => "progress bar",
+            Role::PushButton => "push button",
+            Role::Separator => "separator",
+            Role::Text => "text",
+            Role::ToggleButton => "toggle button",
+            Role::ToolBar => "tool bar",
+            Role::ToolTip => "tool tip",
+            Role::Window => "window",
+            Role::Application => "application",
+        }
+    }
+
+    /// Whether the role is something that gets clicked
+    pub(crate) fn is_clickable(self) -> bool {
+        matches!(
+            self,
+            Role::PushButton
+                | Role::ToggleButton
+                | Role::CheckBox
+                | Role::ListItem
+                | Role::MenuItem
+                | Role::CheckMenuItem
+        )
+    }
+}
+
+// AT-SPI state bits
+const STATE_ACTIVE: u32 = 1;
+const STATE_CHECKED: u32 = 4;
+const STATE_EDITABLE: u32 = 7;
+const STATE_ENABLED: u32 = 8;
+const STATE_FOCUSABLE: u32 = 11;
+const STATE_FOCUSED: u32 = 12;
+const STATE_SENSITIVE: u32 = 24;
+const STATE_SHOWING: u32 = 25;
+const STATE_SINGLE_LINE: u32 = 26;
+const STATE_VISIBLE: u32 = 30;
+const STATE_CHECKABLE: u32 = 41;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Node {
+    pub role: Role,
+    pub name: String,
+    pub description: String,
+    pub bounds: Rectangle,
+    pub checked: Option<bool>,
+    pub focusable: bool,
+    pub focused: bool,
+    pub action: Option<String>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+impl Node {
+    pub fn new(role: Role, name: impl Into<String>, bounds: Rectangle) -> Node {
+        Node {
+            role,
+            name: name.into(),
+            description: String::new(),
+            bounds,
+            checked: None,
+            focusable: role.is_clickable() || matches!(role, Role::Text | Role::PasswordText),
+            focused: false,
+            action: if role.is_clickable() {
+                Some("click".to_owned())
+            } else {
+                None
+            },
+            parent: None,
+            children: Vec::new(),
+        }
+    }
+
+    fn states(&self) -> Vec<u32> {
+        let mut states = vec![STATE_ENABLED, STATE_SENSITIVE, STATE_VISIBLE, STATE_SHOWING];
+        if self.focusable || self.focused {
+            states.push(STATE_FOCUSABLE);
+        }
+        if self.focused {
+            states.push(STATE_FOCUSED);
+        }
+        if let Some(checked) = self.checked {
+            states.push(STATE_CHECKABLE);
+            if checked {
+                states.push(STATE_CHECKED);
+            }
+        }
+        if matches!(self.role, Role::Text | Role::PasswordText) {
+            states.push(STATE_EDITABLE);
+            states.push(STATE_SINGLE_LINE);
+        }
+        states
+    }
+}
+
+/// What assistive technologies ask a surface to do
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Request {
+    /// Perform the default action (click) of the widget with these bounds
+    Activate(Rectangle),
+    /// Move the keyboard focus to the widget with these bounds
+    Focus(Rectangle),
+}
+
+struct Window {
+    id: u32,
+    name: String,
+    active: bool,
+    nodes: Vec<Node>,
+    requests: mpsc::UnboundedSender<Request>,
+}
+
+#[derive(Default)]
+struct Bridge {
+    started: bool,
+    conn: Option<gio::DBusConnection>,
+    interfaces: Option<gio::DBusNodeInfo>,
+    registry_parent: Option<(String, String)>,
+    app_id: i32,
+    windows: Vec<Window>,
+    next_window: u32,
+    registrations: HashMap<String, Vec<gio::RegistrationId>>,
+}
+
+thread_local! {
+    static BRIDGE: RefCell<Bridge> = RefCell::new(Bridge::default());
+    static COLLECTED: RefCell<Option<(Vec<Node>, Vec<usize>)>> = RefCell::new(None);
+}
+
+enum Obj {
+    Root,
+    Window(u32),
+    Node(u32, usize),
+}
+
+fn parse_path(path: &str) -> Option<Obj> {
+    if path == ROOT_PATH {
+        return Some(Obj::Root);
+    }
+    let rest = path.strip_prefix(PATH_PREFIX)?;
+    match rest.split_once('_') {
+        Some((w, n)) => Some(Obj::Node(w.parse().ok()?, n.parse().ok()?)),
+        None => Some(Obj::Window(rest.parse().ok()?)),
+    }
+}
+
+fn window_path(w: u32) -> String {
+    format!("{}{}", PATH_PREFIX, w)
+}
+
+fn node_path(w: u32, n: usize) -> String {
+    format!("{}{}_{}", PATH_PREFIX, w, n)
+}
+
+fn obj_ref(bus: &str, path: &str) -> glib::Variant {
+    glib::Variant::tuple_from_iter([bus.to_variant(), ObjectPath::try_from(path).unwrap().to_variant()])
+}
+
+fn obj_refs(bus: &str, paths: impl Iterator<Item = String>) -> glib::Variant {
+    glib::Variant::array_from_iter_with_type(
+        glib::VariantTy::new("(so)").unwrap(),
+        paths.map(|p| obj_ref(bus, &p)).collect::<Vec<_>>(),
+    )
+}
+
+/// Registers a surface, returns its id and the receiver for its requests
+pub(crate) fn register_window(name: String) -> (u32, mpsc::UnboundedReceiver<Request>) {
+    let (tx, rx) = mpsc::unbounded();
+    let (id, start) = BRIDGE.with(|b| {
+        let mut b = b.borrow_mut();
+        let id = b.next_window;
+        b.next_window += 1;
+        b.windows.push(Window {
+            id,
+            name,
+            active: false,
+            nodes: Vec::new(),
+            requests: tx,
+        });
+        let start = !b.started;
+        b.started = true;
+        (id, start)
+    });
+    if start {
+        glib::MainContext::default().spawn_local(connect());
+    } else {
+        sync_registrations();
+        emit_children_changed_on_root("add", id);
+    }
+    (id, rx)
+}
+
+pub(crate) fn unregister_window(id: u32) {
+    emit_children_changed_on_root("remove", id);
+    BRIDGE.with(|b| b.borrow_mut().windows.retain(|w| w.id != id));
+    sync_registrations();
+}
+
+/// Starts collecting nodes from the Accessible widgets drawn after this call
+pub(crate) fn start_collecting() {
+    COLLECTED.with(|c| c.replace(Some((Vec::new(), Vec::new()))));
+}
+
+/// Adds a node, the nodes added until the matching `end_node` are its children
+pub(crate) fn begin_node(mut node: Node) {
+    COLLECTED.with(|c| {
+        if let Some((ref mut nodes, ref mut stack)) = *c.borrow_mut() {
+            let idx = nodes.len();
+            node.parent = stack.last().cloned();
+            if let Some(&parent) = stack.last() {
+                nodes[parent].children.push(idx);
+            }
+            nodes.push(node);
+            stack.push(idx);
+        }
+    });
+}
+
+pub(crate) fn end_node() {
+    COLLECTED.with(|c| {
+        if let Some((_, ref mut stack)) = *c.borrow_mut() {
+            stack.pop();
+        }
+    });
+}
+
+/// Adds a node without children
+pub(crate) fn add_node(node: Node) {
+    begin_node(node);
+    end_node();
+}
+
+/// Stops collecting and updates the window's tree, notifying about focus and name changes
+pub(crate) fn finish_collecting(id: u32, active: bool) {
+    let nodes = match COLLECTED.with(|c| c.take()) {
+        Some((nodes, _)) => nodes,
+        None => return,
+    };
+    let (old, conn) = match BRIDGE.with(|b| {
+        let mut b = b.borrow_mut();
+        let conn = b.conn.clone();
+        let w = b.windows.iter_mut().find(|w| w.id == id)?;
+        w.active = active;
+        if w.nodes == nodes {
+            return None;
+        }
+        let old = std::mem::replace(&mut w.nodes, nodes.clone());
+        Some((old, conn))
+    }) {
+        Some(x) => x,
+        None => return,
+    };
+    if old.len() != nodes.len() {
+        sync_registrations();
+    }
+    let conn = match conn {
+        Some(c) => c,
+        None => return,
+    };
+
+    let old_focus = old.iter().position(|n| n.focused);
+    let new_focus = nodes.iter().position(|n| n.focused);
+    for (i, node) in nodes.iter().enumerate() {
+        let path = node_path(id, i);
+        let prev = old.get(i);
+        if prev.map(|p| p.name != node.name).unwrap_or(false) {
+            emit(
+                &conn,
+                &path,
+                "PropertyChange",
+                "accessible-name",
+                0,
+                0,
+                node.name.to_variant(),
+            );
+        }
+        if let (Some(Some(was)), Some(now)) = (prev.map(|p| p.checked), node.checked) {
+            if was != now {
+                emit(
+                    &conn,
+                    &path,
+                    "StateChanged",
+                    "checked",
+                    now as i32,
+                    0,
+                    0i32.to_variant(),
+                );
+            }
+        }
+    }
+    if new_focus != old_focus || (new_focus.is_some() && old.len() != nodes.len()) {
+        if let Some(i) = old_focus.filter(|i| *i < nodes.len()) {
+            emit(
+                &conn,
+                &node_path(id, i),
+                "StateChanged",
+                "focused",
+                0,
+                0,
+                0i32.to_variant(),
+            );
+        }
+        if let Some(i) = new_focus {
+            let path = node_path(id, i);
+            emit(&conn, &path, "StateChanged", "focused", 1, 0, 0i32.to_variant());
+            let params = glib::Variant::tuple_from_iter([
+                "".to_variant(),
+                0i32.to_variant(),
+                0i32.to_variant(),
+                glib::Variant::from_variant(&0i32.to_variant()),
+                HashMap::<String, glib::Variant>::new().to_variant(),
+            ]);
+            if let Err(e) = conn.emit_signal(None, &path, "org.a11y.atspi.Event.Focus", "Focus", Some(&params)) {
+                eprintln!("Could not send accessibility focus event: {:?}", e);
+            }
+        }
+    }
+}
+
+fn emit(conn: &gio::DBusConnection, path: &str, signal: &str, kind: &str, d1: i32, d2: i32, data: glib::Variant) {
+    let params = glib::Variant::tuple_from_iter([
+        kind.to_variant(),
+        d1.to_variant(),
+        d2.to_variant(),
+        glib::Variant::from_variant(&data),
+        HashMap::<String, glib::Variant>::new().to_variant(),
+    ]);
+    if let Err(e) = conn.emit_signal(None, path, "org.a11y.atspi.Event.Object", signal, Some(&params)) {
+        eprintln!("Could not send accessibility event: {:?}", e);
+    }
+}
+
+fn emit_children_changed_on_root(kind: &str, window: u32) {
+    let (conn, idx) = match BRIDGE.with(|b| {
+        let b = b.borrow();
+        let idx = b.windows.iter().position(|w| w.id == window)?;
+        Some((b.conn.clone()?, idx))
+    }) {
+        Some(x) => x,
+        None => return,
+    };
+    if let Some(bus) = conn.unique_name() {
+        emit(
+            &conn,
+            ROOT_PATH,
+            "ChildrenChanged",
+            kind,
+            idx as i32,
+            0,
+            obj_ref(&bus, &window_path(window)),
+        );
+    }
+}
+
+async fn bus_address() -> Result<String, glib::Error> {
+    if let Ok(addr) = std::env::var("AT_SPI_BUS_ADDRESS") {
+        return Ok(addr);
+    }
+    let session = gio::bus_get_future(gio::BusType::Session).await?;
+    let reply = session
+        .call_future(
+            Some("org.a11y.Bus"),
+            "/org/a11y/bus",
+            "org.a11y.Bus",
+            "GetAddress",
+            None,
+            Some(glib::VariantTy::new("(s)").unwrap()),
+            gio::DBusCallFlags::NONE,
+            -1,
+        )
+        .await?;
+    Ok(reply.child_value(0).get::<String>().unwrap_or_default())
+}
+
+async fn connect() {
+    let addr = match bus_address().await {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("No accessibility bus: {:?}", e);
+            return;
+        }
+    };
+    let conn = match gio::DBusConnection::for_address_future(
+        &addr,
+        gio::DBusConnectionFlags::AUTHENTICATION_CLIENT | gio::DBusConnectionFlags::MESSAGE_BUS_CONNECTION,
+        None,
+    )
+    .await
+    {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Could not connect to the accessibility bus: {:?}", e);
+            return;
+        }
+    };
+    BRIDGE.with(|b| {
+        let mut b = b.borrow_mut();
+        b.conn = Some(conn.clone());
+        b.interfaces = Some(gio::DBusNodeInfo::for_xml(INTERFACES).unwrap());
+    });
+    sync_registrations();
+
+    let bus = conn.unique_name().map(|n| n.to_string()).unwrap_or_default();
+    match conn
+        .call_future(
+            Some("org.a11y.atspi.Registry"),
+            ROOT_PATH,
+            "org.a11y.atspi.Socket",
+            "Embed",
+            Some(&glib::Variant::tuple_from_iter([obj_ref(&bus, ROOT_PATH)])),
+            Some(glib::VariantTy::new("((so))").unwrap()),
+            gio::DBusCallFlags::NONE,
+            -1,
+        )
+        .await
+    {
+        Ok(reply) => {
+            let parent = reply.child_value(0);
+            let bus = parent.child_value(0).get::<String>().unwrap_or_default();
+            let path = parent.child_value(1).get::<ObjectPath>().map(|p| p.to_string());
+            BRIDGE.with(|b| b.borrow_mut().registry_parent = path.map(|p| (bus, p)));
+        }
+        Err(e) => eprintln!("Could not register with the accessibility registry: {:?}", e),
+    }
+}
+
+/// Makes the registered object paths match the windows and their nodes
+fn sync_registrations() {
+    let (conn, interfaces, wanted) = match BRIDGE.with(|b| {
+        let b = b.borrow();
+        let mut wanted = vec![(
+            ROOT_PATH.to_owned(),
+            vec!["org.a11y.atspi.Accessible", "org.a11y.atspi.Application"],
+        )];
+        for w in b.windows.iter() {
+            wanted.push((window_path(w.id), vec!["org.a11y.atspi.Accessible"]));
+            for (i, node) in w.nodes.iter().enumerate() {
+                let mut ifaces = vec!["org.a11y.atspi.Accessible", "org.a11y.atspi.Component"];
+                if node.action.is_some() {
+                    ifaces.push("org.a11y.atspi.Action");
+                }
+                wanted.push((node_path(w.id, i), ifaces));
+            }
+        }
+        Some((b.conn.clone()?, b.interfaces.clone()?, wanted))
+    }) {
+        Some(x) => x,
+        None => return,
+    };
+
+    let mut registrations = BRIDGE.with(|b| std::mem::take(&mut b.borrow_mut().registrations));
+    registrations.retain(|path, ids| {
+        // re-register if the set of interfaces changed (e.g. an action appeared)
+        let keep = wanted.iter().any(|(p, ifaces)| p == path && ifaces.len() == ids.len());
+        if !keep {
+            for id in ids.drain(..) {
+                let _ = conn.unregister_object(id);
+            }
+        }
+        keep
+    });
+    for (path, ifaces) in wanted {
+        if registrations.contains_key(&path) {
+            continue;
+        }
+        let mut ids = Vec::new();
+        for iface in ifaces {
+            let info = interfaces.lookup_interface(iface).unwrap();
+            match conn.register_object(
+                &path,
+                &info,
+                |conn, _sender, path, iface, method, params, invocation| {
+                    let reply = handle_method(&conn, path, iface, method, &params);
+                    match reply {
+                        Some(reply) => invocation.return_value(Some(&reply)),
+                        None => invocation.return_dbus_error("org.freedesktop.DBus.Error.UnknownObject", path),
+                    }
+                },
+                |conn, _sender, path, iface, prop| {
+                    get_property(&conn, path, iface, prop).unwrap_or_else(|| "".to_variant())
+                },
+                |_conn, _sender, path, _iface, prop, value| {
+                    if path == ROOT_PATH && prop == "Id" {
+                        if let Some(id) = value.get::<i32>() {
+                            BRIDGE.with(|b| b.borrow_mut().app_id = id);
+                            return true;
+                        }
+                    }
+                    false
+                },
+            ) {
+                Ok(id) => ids.push(id),
+                Err(e) => eprintln!("Could not register accessible {}: {:?}", path, e),
+            }
+        }
+        registrations.insert(path, ids);
+    }
+    BRIDGE.with(|b| b.borrow_mut().registrations = registrations);
+}
+
+/// Everything the method handlers need to know about an object
+struct Info {
+    role: Role,
+    name: String,
+    description: String,
+    parent: (String, String),
+    children: Vec<String>,
+    index_in_parent: i32,
+    states: Vec<u32>,
+    bounds: Option<Rectangle>,
+    action: Option<String>,
+    window: Option<(u32, mpsc::UnboundedSender<Request>)>,
+    interfaces: Vec<&'static str>,
+}
+
+fn info(bus: &str, path: &str) -> Option<Info> {
+    BRIDGE.with(|b| {
+        let b = b.borrow();
+        match parse_path(path)? {
+            Obj::Root => Some(Info {
+                role: Role::Application,
+                name: glib::prgname().map(|n| n.to_string()).unwrap_or_default(),
+                description: String::new(),
+                parent: b
+                    .registry_parent
+                    .clone()
+                    .unwrap_or_else(|| (String::new(), NULL_PATH.to_owned())),
+                children: b.windows.iter().map(|w| window_path(w.id)).collect(),
+                index_in_parent: -1,
+                states: vec![],
+                bounds: None,
+                action: None,
+                window: None,
+                interfaces: vec!["org.a11y.atspi.Accessible", "org.a11y.atspi.Application"],
+            }),
+            Obj::Window(id) => {
+                let (idx, w) = b.windows.iter().enumerate().find(|(_, w)| w.id == id)?;
+                let mut states = vec![STATE_ENABLED, STATE_SENSITIVE, STATE_VISIBLE, STATE_SHOWING];
+                if w.active {
+                    states.push(STATE_ACTIVE);
+                }
+                Some(Info {
+                    role: Role::Window,
+                    name: w.name.clone(),
+                    description: String::new(),
+                    parent: (bus.to_owned(), ROOT_PATH.to_owned()),
+                    children: w
+                        .nodes
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, n)| n.parent.is_none())
+                        .map(|(i, _)| node_path(id, i))
+                        .collect(),
+                    index_in_parent: idx as i32,
+                    states,
+                    bounds: None,
+                    action: None,
+                    window: None,
+                    interfaces: vec!["org.a11y.atspi.Accessible"],
+                })
+            }
+            Obj::Node(id, n) => {
+                let w = b.windows.iter().find(|w| w.id == id)?;
+                let node = w.nodes.get(n)?;
+                let siblings = match node.parent {
+                    Some(p) => w.nodes[p].children.clone(),
+                    None => (0..w.nodes.len()).filter(|i| w.nodes[*i].parent.is_none()).collect(),
+                };
+                let mut interfaces = vec!["org.a11y.atspi.Accessible", "org.a11y.atspi.Component"];
+                if node.action.is_some() {
+                    interfaces.push("org.a11y.atspi.Action");
+                }
+                Some(Info {
+                    role: node.role,
+                    name: node.name.clone(),
+                    description: node.description.clone(),
+                    parent: (
+                        bus.to_owned(),
+                        node.parent.map(|p| node_path(id, p)).unwrap_or_else(|| window_path(id)),
+                    ),
+                    children: node.children.iter().map(|c| node_path(id, *c)).collect(),
+                    index_in_parent: siblings.iter().position(|s| *s == n).map(|i| i as i32).unwrap_or(-1),
+                    states: node.states(),
+                    bounds: Some(node.bounds),
+                    action: node.action.clone(),
+                    window: Some((id, w.requests.clone())),
+                    interfaces,
+                })
+            }
+        }
+    })
+}
+
+fn state_set(states: &[u32]) -> glib::Variant {
+    let mut bits = [0u32; 2];
+    for s in states {
+        bits[(*s / 32) as usize] |= 1 << (*s % 32);
+    }
+    bits.to_vec().to_variant()
+}
+
+fn handle_method(
+    conn: &gio::DBusConnection,
+    path: &str,
+    iface: &str,
+    method: &str,
+    params: &glib::Variant,
+) -> Option<glib::Variant> {
+    let bus = conn.unique_name()?.to_string();
+    let info = info(&bus, path)?;
+    let tuple = |v: glib::Variant| glib::Variant::tuple_from_iter([v]);
+    let reply = match (iface, method) {
+        ("org.a11y.atspi.Accessible", "GetChildAtIndex") => {
+            let idx = params.child_value(0).get::<i32>()?;
+            let child = usize::try_from(idx).ok().and_then(|i| info.children.get(i).cloned());
+            tuple(match child {
+                Some(c) => obj_ref(&bus, &c),
+                None => obj_ref("", NULL_PATH),
+            })
+        }
+        ("org.a11y.atspi.Accessible", "GetChildren") => tuple(obj_refs(&bus, info.children.into_iter())),
+        ("org.a11y.atspi.Accessible", "GetIndexInParent") => tuple(info.index_in_parent.to_variant()),
+        ("org.a11y.atspi.Accessible", "GetRelationSet") => tuple(glib::Variant::array_from_iter_with_type(
+            glib::VariantTy::new("(ua(so))").unwrap(),
+            Vec::<glib::Variant>::new(),
+        )),
+        ("org.a11y.atspi.Accessible", "GetRole") => tuple(info.role.atspi().to_variant()),
+        ("org.a11y.atspi.Accessible", "GetRoleName") | ("org.a11y.atspi.Accessible", "GetLocalizedRoleName") => {
+            tuple(info.role.name().to_variant())
+        }
+        ("org.a11y.atspi.Accessible", "GetState") => tuple(state_set(&info.states)),
+        ("org.a11y.atspi.Accessible", "GetAttributes") => {
+            let mut attrs = HashMap::new();
+            attrs.insert("toolkit".to_owned(), "wstk".to_owned());
+            tuple(attrs.to_variant())
+        }
+        ("org.a11y.atspi.Accessible", "GetApplication") => tuple(obj_ref(&bus, ROOT_PATH)),
+        ("org.a11y.atspi.Accessible", "GetInterfaces") => tuple(
+            info.interfaces
+                .iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .to_variant(),
+        ),
+        ("org.a11y.atspi.Application", "GetLocale") => {
+            tuple(std::env::var("LANG").unwrap_or_else(|_| "C".to_owned()).to_variant())
+        }
+        ("org.a11y.atspi.Action", "GetDescription") | ("org.a11y.atspi.Action", "GetKeyBinding") => {
+            tuple("".to_variant())
+        }
+        ("org.a11y.atspi.Action", "GetName") | ("org.a11y.atspi.Action", "GetLocalizedName") => {
+            tuple(info.action.unwrap_or_default().to_variant())
+        }
+        ("org.a11y.atspi.Action", "GetActions") => tuple(
+            info.action
+                .into_iter()
+                .map(|a| (a, String::new(), String::new()))
+                .collect::<Vec<_>>()
+                .to_variant(),
+        ),
+        ("org.a11y.atspi.Action", "DoAction") => {
+            let ok = match (info.window, info.bounds) {
+                (Some((_, tx)), Some(bounds)) => tx.unbounded_send(Request::Activate(bounds)).is_ok(),
+                _ => false,
+            };
+            tuple(ok.to_variant())
+        }
+        ("org.a11y.atspi.Component", "Contains") => {
+            let x = params.child_value(0).get::<i32>()?;
+            let y = params.child_value(1).get::<i32>()?;
+            let inside = info
+                .bounds
+                .map(|b| b.contains(Point::new(x as _, y as _)))
+                .unwrap_or(false);
+            tuple(inside.to_variant())
+        }
+        ("org.a11y.atspi.Component", "GetExtents") => {
+            // layer surfaces don't know where they are on the screen, so these are surface coordinates
+            let b = info.bounds.unwrap_or_default();
+            tuple((b.x as i32, b.y as i32, b.width as i32, b.height as i32).to_variant())
+        }
+        ("org.a11y.atspi.Component", "GetPosition") => {
+            let b = info.bounds.unwrap_or_default();
+            (b.x as i32, b.y as i32).to_variant()
+        }
+        ("org.a11y.atspi.Component", "GetSize") => {
+            let b = info.bounds.unwrap_or_default();
+            (b.width as i32, b.height as i32).to_variant()
+        }
+        ("org.a11y.atspi.Component", "GrabFocus") => {
+            let ok = match (info.window, info.bounds) {
+                (Some((_, tx)), Some(bounds)) => tx.unbounded_send(Request::Focus(bounds)).is_ok(),
+                _ => false,
+            };
+            tuple(ok.to_variant())
+        }
+        _ => return None,
+    };
+    Some(reply)
+}
+
+fn get_property(conn: &gio::DBusConnection, path: &str, iface: &str, prop: &str) -> Option<glib::Variant> {
+    let bus = conn.unique_name()?.to_string();
+    if iface == "org.a11y.atspi.Application" {
+        return Some(match prop {
+            "ToolkitName" => "wstk".to_variant(),
+            "Version" => env!("CARGO_PKG_VERSION").to_variant(),
+            "AtspiVersion" => "2.1".to_variant(),
+            "Id" => BRIDGE.with(|b| b.borrow().app_id).to_variant(),
+            _ => return None,
+        });
+    }
+    let info = info(&bus, path)?;
+    Some(match (iface, prop) {
+        ("org.a11y.atspi.Accessible", "Name") => info.name.to_variant(),
+        ("org.a11y.atspi.Accessible", "Description") => info.description.to_variant(),
+        ("org.a11y.atspi.Accessible", "Parent") => obj_ref(&info.parent.0, &info.parent.1),
+        ("org.a11y.atspi.Accessible", "ChildCount") => (info.children.len() as i32).to_variant(),
+        ("org.a11y.atspi.Accessible", "Locale") => std::env::var("LANG").unwrap_or_default().to_variant(),
+        ("org.a11y.atspi.Accessible", "AccessibleId") => "".to_variant(),
+        ("org.a11y.atspi.Action", "NActions") => (info.action.is_some() as i32).to_variant(),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect() -> Rectangle {
+        Rectangle::new(Point::new(1.0, 2.0), iced_native::Size::new(3.0, 4.0))
+    }
+
+    /// A window that's known to the bridge without a bus connection (the bridge is per thread, like each test)
+    fn add_window(id: u32) {
+        BRIDGE.with(|b| {
+            b.borrow_mut().windows.push(Window {
+                id,
+                name: "test".to_owned(),
+                active: false,
+                nodes: Vec::new(),
+                requests: mpsc::unbounded().0,
+            })
+        });
+    }
+
+    fn nodes(id: u32) -> Vec<Node> {
+        BRIDGE.with(|b| b.borrow().windows.iter().find(|w| w.id == id).unwrap().nodes.clone())
+    }
+
+    #[test]
+    fn state_set_bits() {
+        assert_eq!(state_set(&[]).get::<Vec<u32>>(), Some(vec![0, 0]));
+        assert_eq!(
+            state_set(&[STATE_ACTIVE, STATE_ENABLED, STATE_VISIBLE, STATE_CHECKABLE]).get::<Vec<u32>>(),
+            Some(vec![1 << 1 | 1 << 8 | 1 << 30, 1 << 9])
+        );
+        assert_eq!(state_set(&[STATE_FOCUSED, STATE_FOCUSED]), state_set(&[STATE_FOCUSED]));
+    }
+
+    #[test]
+    fn node_states() {
+        let label = Node::new(Role::Label, "label", rect());
+        assert_eq!(
+            label.states(),
+            vec![STATE_ENABLED, STATE_SENSITIVE, STATE_VISIBLE, STATE_SHOWING]
+        );
+
+        let mut check = Node::new(Role::CheckBox, "check", rect());
+        check.checked = Some(true);
+        check.focused = true;
+        let states = check.states();
+        for s in [STATE_FOCUSABLE, STATE_FOCUSED, STATE_CHECKABLE, STATE_CHECKED] {
+            assert!(states.contains(&s));
+        }
+        check.checked = Some(false);
+        assert!(check.states().contains(&STATE_CHECKABLE));
+        assert!(!check.states().contains(&STATE_CHECKED));
+
+        let text = Node::new(Role::PasswordText, "password", rect());
+        let states = text.states();
+        for s in [STATE_FOCUSABLE, STATE_EDITABLE, STATE_SINGLE_LINE] {
+            assert!(states.contains(&s));
+        }
+        assert!(!states.contains(&STATE_FOCUSED));
+        assert_eq!(text.action, None);
+        assert_eq!(
+            Node::new(Role::MenuItem, "item", rect()).action.as_deref(),
+            Some("click")
+        );
+    }
+
+    #[test]
+    fn roles() {
+        assert_eq!(Role::PushButton.atspi(), 43);
+        assert_eq!(Role::Window.atspi(), 69);
+        assert_eq!(Role::Application.atspi(), 75);
+        assert_eq!(Role::ListItem.name(), "list item");
+        assert!(Role::CheckMenuItem.is_clickable());
+        assert!(!Role::Label.is_clickable());
+    }
+
+    #[test]
+    fn paths() {
+        assert!(matches!(parse_path(ROOT_PATH), Some(Obj::Root)));
+        assert!(matches!(parse_path(&window_path(3)), Some(Obj::Window(3))));
+        assert!(matches!(parse_path(&node_path(3, 14)), Some(Obj::Node(3, 14))));
+        assert!(parse_path(NULL_PATH).is_none());
+        assert!(parse_path(&format!("{}x_1", PATH_PREFIX)).is_none());
+    }
+
+    #[test]
+    fn collected_tree() {
+        add_window(0);
+        start_collecting();
+        begin_node(Node::new(Role::Panel, "panel", rect()));
+        add_node(Node::new(Role::PushButton, "ok", rect()));
+        begin_node(Node::new(Role::List, "list", rect()));
+        add_node(Node::new(Role::ListItem, "one", rect()));
+        add_node(Node::new(Role::ListItem, "two", rect()));
+        end_node();
+        end_node();
+        add_node(Node::new(Role::Label, "status", rect()));
+        finish_collecting(0, true);
+
+        let tree = nodes(0);
+        assert_eq!(
+            tree.iter().map(|n| n.name.as_str()).collect::<Vec<_>>(),
+            vec!["panel", "ok", "list", "one", "two", "status"]
+        );
+        assert_eq!(tree[0].children, vec![1, 2]);
+        assert_eq!(tree[2].children, vec![3, 4]);
+        assert_eq!(tree[4].parent, Some(2));
+        assert_eq!(tree[5].parent, None);
+
+        let window = info("bus", &window_path(0)).unwrap();
+        assert_eq!(window.children, vec![node_path(0, 0), node_path(0, 5)]);
+        assert!(window.states.contains(&STATE_ACTIVE));
+
+        let two = info("bus", &node_path(0, 4)).unwrap();
+        assert_eq!(two.parent, ("bus".to_owned(), node_path(0, 2)));
+        assert_eq!(two.index_in_parent, 1);
+        assert!(two.interfaces.contains(&"org.a11y.atspi.Action"));
+
+        let status = info("bus", &node_path(0, 5)).unwrap();
+        assert_eq!(status.parent, ("bus".to_owned(), window_path(0)));
+        assert_eq!(status.index_in_parent, 1);
+        assert!(!status.interfaces.contains(&"org.a11y.atspi.Action"));
+        assert!(info("bus", &node_path(0, 6)).is_none());
+
+        // the next frame replaces the tree
+        start_collecting();
+        add_node(Node::new(Role::Label, "only", rect()));
+        finish_collecting(0, false);
+        let tree = nodes(0);
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree[0].parent, None);
+        assert!(!info("bus", &window_path(0)).unwrap().states.contains(&STATE_ACTIVE));
+    }
+
+    #[test]
+    fn nodes_outside_collecting_are_ignored() {
+        add_window(0);
+        add_node(Node::new(Role::Label, "stray", rect()));
+        finish_collecting(0, false);
+        assert!(nodes(0).is_empty());
+    }
+}
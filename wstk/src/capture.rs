@@ -0,0 +1,405 @@
+//! Window thumbnails, e.g. for previews in the dock.
+//! Captured with ext-image-copy-capture from a toplevel capture source when the compositor has those,
+//! otherwise with wlr-screencopy of the output, cropped to where the window is.
+//! The toplevel protocols don't say where windows are, so that fallback is only used for active windows
+//! on a single output whose position follows from their state: fullscreen ones cover the whole output,
+//! maximized ones cover it except for the space panels reserve, as told by `CaptureService::reserve`.
+
+pub use crate::protocols::{
+    ext_image_capture_source::ext_foreign_toplevel_image_capture_source_manager_v1 as toplevel_source_manager,
+    ext_image_copy_capture::{
+        ext_image_copy_capture_frame_v1 as copy_frame, ext_image_copy_capture_manager_v1 as copy_manager,
+        ext_image_copy_capture_session_v1 as copy_session,
+    },
+};
+pub use smithay_client_toolkit::reexports::protocols::wlr::unstable::screencopy::v1::client::{
+    zwlr_screencopy_frame_v1 as screencopy_frame, zwlr_screencopy_manager_v1 as screencopy_manager,
+};
+
+use crate::{event_loop::wayland_event_chan, surfaces::*, toplevels::*, ImageHandle};
+use smithay_client_toolkit::{reexports::client::protocol::wl_buffer, shm::MemPool};
+use std::{cell::RefCell, rc::Rc};
+
+/// Both are BGRA in memory (little endian ARGB), like iced wants pixels
+const SUPPORTED_FORMATS: &[wl_shm::Format] = &[wl_shm::Format::Argb8888, wl_shm::Format::Xrgb8888];
+
+/// Runs the closure when dropped, to destroy protocol objects even if a capture future is dropped midway
+struct Defer<F: FnMut()>(F);
+
+impl<F: FnMut()> Drop for Defer<F> {
+    fn drop(&mut self) {
+        (self.0)()
+    }
+}
+
+/// A buffer for the compositor to copy into, destroyed on drop
+/// (unlike buffers attached to surfaces, it never gets released)
+struct ShmBuffer {
+    pool: MemPool,
+    buffer: wl_buffer::WlBuffer,
+    len: usize,
+}
+
+impl ShmBuffer {
+    fn new(env: &Environment<Env>, width: u32, height: u32, stride: u32, format: wl_shm::Format) -> Option<ShmBuffer> {
+        let len = (stride * height) as usize;
+        let pool = env
+            .create_simple_pool(|_| ())
+            .and_then(|mut pool| pool.resize(len).map(|()| pool))
+            .map_err(|e| eprintln!("Could not create a capture buffer: {:?}", e))
+            .ok()?;
+        let buffer = pool.buffer(0, width as _, height as _, stride as _, format);
+        Some(ShmBuffer { pool, buffer, len })
+    }
+
+    fn pixels(&mut self) -> Vec<u8> {
+        self.pool.mmap()[..self.len].to_vec()
+    }
+}
+
+impl Drop for ShmBuffer {
+    fn drop(&mut self) {
+        self.buffer.destroy();
+    }
+}
+
+/// Space reserved along the edges of an output (like layer-shell exclusive zones), in surface-local pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Insets {
+    pub top: u32,
+    pub bottom: u32,
+    pub left: u32,
+    pub right: u32,
+}
+
+impl Insets {
+    fn scaled(self, scale: u32) -> Insets {
+        Insets {
+            top: self.top * scale,
+            bottom: self.bottom * scale,
+            left: self.left * scale,
+            right: self.right * scale,
+        }
+    }
+}
+
+/// Pixels copied by the compositor
+struct Frame {
+    width: u32,
+    height: u32,
+    stride: u32,
+    format: wl_shm::Format,
+    y_invert: bool,
+    pixels: Vec<u8>,
+}
+
+impl Frame {
+    /// Without the insets around the edges, None if nothing is left
+    fn crop(self, insets: Insets) -> Option<Frame> {
+        let width = self.width.checked_sub(insets.left + insets.right).filter(|w| *w > 0)?;
+        let height = self.height.checked_sub(insets.top + insets.bottom).filter(|h| *h > 0)?;
+        // the rows are stored bottom to top when inverted
+        let first_row = if self.y_invert { insets.bottom } else { insets.top };
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in first_row..first_row + height {
+            let start = (y * self.stride + insets.left * 4) as usize;
+            pixels.extend_from_slice(&self.pixels[start..start + (width * 4) as usize]);
+        }
+        Some(Frame {
+            width,
+            height,
+            stride: width * 4,
+            pixels,
+            ..self
+        })
+    }
+
+    /// Scaled down to at most `max_width` by skipping pixels, good enough for a preview.
+    /// None for an empty frame.
+    fn thumbnail(&self, max_width: u32) -> Option<ImageHandle> {
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+        let max_width = max_width.max(1);
+        let step = ((self.width + max_width - 1) / max_width).max(1);
+        let (width, height) = (self.width / step, self.height / step);
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            let src_y = if self.y_invert {
+                self.height - 1 - y * step
+            } else {
+                y * step
+            };
+            let row = (src_y * self.stride) as usize;
+            for x in 0..width {
+                let i = row + (x * step * 4) as usize;
+                pixels.extend_from_slice(&self.pixels[i..i + 3]);
+                pixels.push(if self.format == wl_shm::Format::Xrgb8888 {
+                    255
+                } else {
+                    self.pixels[i + 3]
+                });
+            }
+        }
+        Some(ImageHandle::Raster(iced_native::image::Handle::from_pixels(
+            width, height, pixels,
+        )))
+    }
+}
+
+pub struct CaptureService {
+    env: Environment<Env>,
+    display: Display,
+    toplevels: Rc<ToplevelService>,
+    reserved: RefCell<Vec<(wl_output::WlOutput, Insets)>>,
+}
+
+impl CaptureService {
+    pub fn new(env: &Environment<Env>, display: &Display) -> CaptureService {
+        CaptureService {
+            env: env.clone(),
+            display: display.clone(),
+            toplevels: env.with_inner(|i| i.toplevel_service()),
+            reserved: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Tells where panels reserve space on the output, for cropping output captures of maximized windows
+    pub fn reserve(&self, output: &wl_output::WlOutput, insets: Insets) {
+        let mut reserved = self.reserved.borrow_mut();
+        reserved.retain(|(o, _)| o != output);
+        reserved.push((output.clone(), insets));
+    }
+
+    /// Where a window in this state is on its output, None if that's unknown
+    fn window_insets(&self, topl: &ToplevelState, output: &wl_output::WlOutput) -> Option<Insets> {
+        if !topl.state.activated || topl.outputs.len() != 1 {
+            return None;
+        }
+        if topl.state.fullscreen {
+            return Some(Insets::default());
+        }
+        if topl.state.maximized {
+            let reserved = self.reserved.borrow();
+            return Some(
+                reserved
+                    .iter()
+                    .find(|(o, _)| o == output)
+                    .map_or_else(Insets::default, |(_, i)| *i),
+            );
+        }
+        None
+    }
+
+    /// A thumbnail of the window at most `max_width` pixels wide, None if it can't be captured right now
+    pub async fn capture_toplevel(&self, id: ToplevelId, max_width: u32) -> Option<ImageHandle> {
+        // copied out, the states can't stay borrowed while waiting for the compositor
+        let (ext_handle, covered_output) = {
+            let topl = self.toplevels.get(id)?;
+            let covered = topl
+                .outputs
+                .first()
+                .and_then(|o| Some((o.clone(), self.window_insets(&topl, o)?)));
+            (self.toplevels.ext_handle(&topl), covered)
+        };
+        let mut frame = None;
+        if let Some(handle) = ext_handle {
+            frame = self.capture_ext(&handle).await;
+        }
+        if let (None, Some((output, insets))) = (&frame, covered_output) {
+            // the insets are in surface-local pixels, the capture in the output's
+            let scale = output::with_output_info(&output, |i| i.scale_factor)
+                .unwrap_or(1)
+                .max(1) as u32;
+            frame = self
+                .capture_output(&output)
+                .await
+                .and_then(|f| f.crop(insets.scaled(scale)));
+        }
+        frame.and_then(|f| f.thumbnail(max_width))
+    }
+
+    fn flush(&self) {
+        if let Err(e) = self.display.flush() {
+            if e.kind() != std::io::ErrorKind::WouldBlock {
+                eprintln!("Error flushing the wayland socket: {:?}", e);
+            }
+        }
+    }
+
+    async fn capture_ext(&self, handle: &ext_toplevel_handle::ExtForeignToplevelHandleV1) -> Option<Frame> {
+        let sources = self
+            .env
+            .get_global::<toplevel_source_manager::ExtForeignToplevelImageCaptureSourceManagerV1>()?;
+        let manager = self.env.get_global::<copy_manager::ExtImageCopyCaptureManagerV1>()?;
+        let source = sources.create_source(handle);
+        let session = manager.create_session(&source, 0);
+        let _destroy_session = Defer(|| {
+            session.destroy();
+            source.destroy();
+        });
+        let mut session_events = wayland_event_chan(&session);
+        self.flush();
+
+        let (mut size, mut format) = (None, None);
+        loop {
+            match session_events.next().await? {
+                copy_session::Event::BufferSize { width, height } => size = Some((width, height)),
+                copy_session::Event::ShmFormat { format: f } if SUPPORTED_FORMATS.contains(&f) => format = Some(f),
+                copy_session::Event::Done => break,
+                copy_session::Event::Stopped => return None,
+                _ => (),
+            }
+        }
+        let ((width, height), format) = (size?, format?);
+
+        let mut buffer = ShmBuffer::new(&self.env, width, height, width * 4, format)?;
+        let frame = session.create_frame();
+        let _destroy_frame = Defer(|| frame.destroy());
+        let mut frame_events = wayland_event_chan(&frame);
+        frame.attach_buffer(&buffer.buffer);
+        frame.damage_buffer(0, 0, width as _, height as _);
+        frame.capture();
+        self.flush();
+        loop {
+            match frame_events.next().await? {
+                copy_frame::Event::Ready => break,
+                copy_frame::Event::Failed { reason } => {
+                    eprintln!("Could not capture a window: {:?}", reason);
+                    return None;
+                }
+                _ => (),
+            }
+        }
+        Some(Frame {
+            width,
+            height,
+            stride: width * 4,
+            format,
+            y_invert: false,
+            pixels: buffer.pixels(),
+        })
+    }
+
+    async fn capture_output(&self, output: &wl_output::WlOutput) -> Option<Frame> {
+        let manager = self.env.get_global::<screencopy_manager::ZwlrScreencopyManagerV1>()?;
+        let frame = manager.capture_output(0, output);
+        let _destroy_frame = Defer(|| frame.destroy());
+        let mut events = wayland_event_chan(&frame);
+        self.flush();
+
+        let mut info = None;
+        loop {
+            match events.next().await? {
+                screencopy_frame::Event::Buffer {
+                    format,
+                    width,
+                    height,
+                    stride,
+                } => {
+                    if SUPPORTED_FORMATS.contains(&format) {
+                        info = Some((format, width, height, stride));
+                    }
+                    // before version 3, there's no buffer_done and only one buffer event
+                    if frame.as_ref().version() < 3 {
+                        break;
+                    }
+                }
+                screencopy_frame::Event::BufferDone => break,
+                screencopy_frame::Event::Failed => return None,
+                _ => (),
+            }
+        }
+        let (format, width, height, stride) = info?;
+
+        let mut buffer = ShmBuffer::new(&self.env, width, height, stride, format)?;
+        frame.copy(&buffer.buffer);
+        self.flush();
+        let mut y_invert = false;
+        loop {
+            match events.next().await? {
+                screencopy_frame::Event::Flags { flags } => y_invert = flags.contains(screencopy_frame::Flags::YInvert),
+                screencopy_frame::Event::Ready { .. } => break,
+                screencopy_frame::Event::Failed => {
+                    eprintln!("Could not capture an output");
+                    return None;
+                }
+                _ => (),
+            }
+        }
+        Some(Frame {
+            width,
+            height,
+            stride,
+            format,
+            y_invert,
+            pixels: buffer.pixels(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iced_native::image::Data;
+
+    /// Each pixel's blue byte is its index, so it's easy to tell which ones are left
+    fn frame(width: u32, height: u32, y_invert: bool) -> Frame {
+        Frame {
+            width,
+            height,
+            stride: width * 4,
+            format: wl_shm::Format::Argb8888,
+            y_invert,
+            pixels: (0..width * height).flat_map(|i| [i as u8, 0, 0, 255]).collect(),
+        }
+    }
+
+    fn blues(frame: &Frame) -> Vec<u8> {
+        frame.pixels.chunks(4).map(|p| p[0]).collect()
+    }
+
+    #[test]
+    fn crop() {
+        let insets = Insets {
+            top: 1,
+            bottom: 0,
+            left: 1,
+            right: 1,
+        };
+        let cropped = frame(4, 3, false).crop(insets).unwrap();
+        assert_eq!((cropped.width, cropped.height, cropped.stride), (2, 2, 8));
+        assert_eq!(blues(&cropped), vec![5, 6, 9, 10]);
+
+        // the first rows in memory are the bottom ones
+        let cropped = frame(4, 3, true).crop(insets).unwrap();
+        assert_eq!(blues(&cropped), vec![1, 2, 5, 6]);
+        assert!(cropped.y_invert);
+
+        assert!(frame(4, 3, false)
+            .crop(Insets {
+                left: 2,
+                right: 2,
+                ..Insets::default()
+            })
+            .is_none());
+    }
+
+    #[test]
+    fn thumbnail() {
+        match frame(4, 2, false).thumbnail(2).unwrap() {
+            ImageHandle::Raster(handle) => match handle.data() {
+                Data::Pixels { width, height, pixels } => {
+                    assert_eq!((*width, *height), (2, 1));
+                    assert_eq!(pixels.chunks(4).map(|p| p[0]).collect::<Vec<_>>(), vec![0, 2]);
+                }
+                _ => panic!("not pixels"),
+            },
+            _ => panic!("not a raster image"),
+        }
+
+        assert!(frame(0, 0, false).thumbnail(2).is_none());
+        assert!(frame(4, 0, true).thumbnail(2).is_none());
+        assert!(frame(4, 2, false).thumbnail(0).is_some());
+    }
+}
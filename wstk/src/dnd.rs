@@ -0,0 +1,428 @@
+//! Drag and drop, on the same data device the clipboard uses.
+//! The data device callback is global, so events are routed to surfaces here
+//! (like the scale channels), and drop targets are looked up in what the
+//! DropTarget widgets registered during the last draw of each surface.
+
+use crate::{gpu::Gpu, handle::ToRWH, surfaces::*, ImageHandle};
+use futures::channel::mpsc;
+use gio::prelude::*;
+use iced_native::{user_interface::Cache, Element, Length, Point, Rectangle, Size, UserInterface};
+use std::{
+    cell::{Cell, RefCell},
+    io::Write,
+    rc::Rc,
+};
+
+pub use data_device::DndAction;
+
+pub const MIME_URI_LIST: &str = "text/uri-list";
+pub const MIME_TEXT: &str = "text/plain;charset=utf-8";
+
+/// Something dropped onto a DropTarget
+#[derive(Debug, Clone, PartialEq)]
+pub struct DropData {
+    pub mime_type: String,
+    pub data: String,
+    /// Where it was dropped, in surface coordinates
+    pub position: Point,
+}
+
+impl DropData {
+    /// The URIs of a text/uri-list drop (e.g. files from a file manager)
+    pub fn uris(&self) -> Vec<String> {
+        if self.mime_type != MIME_URI_LIST {
+            return Vec::new();
+        }
+        self.data
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::to_owned)
+            .collect()
+    }
+}
+
+/// What a DragSource offers when dragged
+#[derive(Clone)]
+pub struct DragData {
+    /// Mime types with the data for each, in order of preference
+    pub offers: Vec<(String, String)>,
+    /// Shown under the cursor while dragging
+    pub icon: Option<ImageHandle>,
+    pub icon_size: u16,
+}
+
+impl DragData {
+    pub fn new(mime_type: impl Into<String>, data: impl Into<String>) -> Self {
+        DragData {
+            offers: vec![(mime_type.into(), data.into())],
+            icon: None,
+            icon_size: 48,
+        }
+    }
+
+    pub fn offer(mut self, mime_type: impl Into<String>, data: impl Into<String>) -> Self {
+        self.offers.push((mime_type.into(), data.into()));
+        self
+    }
+
+    pub fn icon(mut self, icon: ImageHandle, size: u16) -> Self {
+        self.icon = Some(icon);
+        self.icon_size = size;
+        self
+    }
+}
+
+pub(crate) enum DndEvent {
+    Enter(Point),
+    Motion(Point),
+    Leave,
+    Drop(DropData),
+    /// A drag started from this surface is over, `accepted` is false if nobody took the data
+    DragEnd {
+        id: u64,
+        accepted: bool,
+    },
+}
+
+type Targets = Vec<(Rectangle, Vec<String>)>;
+
+struct Registered {
+    surface: wl_surface::WlSurface,
+    targets: Targets,
+    tx: mpsc::UnboundedSender<DndEvent>,
+}
+
+thread_local! {
+    static SURFACES: RefCell<Vec<Registered>> = RefCell::new(Vec::new());
+    static CURRENT: RefCell<Option<(wl_surface::WlSurface, Point)>> = RefCell::new(None);
+    // The offer of the last drop (while its data is being read) and the surface it was dropped on
+    static DROPPED: RefCell<Option<(*const data_device::DataOffer, wl_surface::WlSurface)>> = RefCell::new(None);
+    static COLLECTED: RefCell<Option<Targets>> = RefCell::new(None);
+    static DELIVERED_DROP: RefCell<Option<DropData>> = RefCell::new(None);
+    static DELIVERED_DRAG_END: Cell<Option<(u64, bool)>> = Cell::new(None);
+    static DRAG_REQUEST: RefCell<Option<(u64, DragData)>> = RefCell::new(None);
+    static NEXT_DRAG_ID: Cell<u64> = Cell::new(1);
+}
+
+pub(crate) fn register(surface: wl_surface::WlSurface) -> mpsc::UnboundedReceiver<DndEvent> {
+    let (tx, rx) = mpsc::unbounded();
+    SURFACES.with(|s| {
+        s.borrow_mut().push(Registered {
+            surface,
+            targets: Vec::new(),
+            tx,
+        })
+    });
+    rx
+}
+
+pub(crate) fn unregister(surface: &wl_surface::WlSurface) {
+    SURFACES.with(|s| s.borrow_mut().retain(|r| r.surface != *surface));
+}
+
+fn send_to(surface: &wl_surface::WlSurface, event: DndEvent) {
+    SURFACES.with(|s| {
+        if let Some(r) = s.borrow().iter().find(|r| r.surface == *surface) {
+            let _ = r.tx.unbounded_send(event);
+        }
+    });
+}
+
+/// Starts collecting drop targets from the DropTarget widgets drawn after this call
+pub(crate) fn start_collecting() {
+    COLLECTED.with(|c| c.replace(Some(Vec::new())));
+}
+
+/// Stops collecting and makes the collected drop targets the current ones for the surface
+pub(crate) fn finish_collecting(surface: &wl_surface::WlSurface) {
+    let targets = COLLECTED.with(|c| c.take()).unwrap_or_default();
+    SURFACES.with(|s| {
+        if let Some(r) = s.borrow_mut().iter_mut().find(|r| r.surface == *surface) {
+            r.targets = targets;
+        }
+    });
+}
+
+pub(crate) fn add_target(bounds: Rectangle, mime_types: Vec<String>) {
+    COLLECTED.with(|c| {
+        if let Some(ref mut targets) = *c.borrow_mut() {
+            targets.push((bounds, mime_types));
+        }
+    });
+}
+
+/// Sets the drop that's about to be processed by the widgets of a surface
+pub(crate) fn deliver_drop(data: DropData) {
+    DELIVERED_DROP.with(|d| d.replace(Some(data)));
+}
+
+/// Takes the delivered drop if it happened inside the bounds
+pub(crate) fn take_drop(bounds: Rectangle, mime_types: &[String]) -> Option<DropData> {
+    DELIVERED_DROP.with(|d| {
+        let mut d = d.borrow_mut();
+        match *d {
+            Some(ref data) if bounds.contains(data.position) && mime_types.contains(&data.mime_type) => d.take(),
+            _ => None,
+        }
+    })
+}
+
+pub(crate) fn deliver_drag_end(id: u64, accepted: bool) {
+    DELIVERED_DRAG_END.with(|d| d.set(Some((id, accepted))));
+}
+
+pub(crate) fn take_drag_end(id: u64) -> Option<bool> {
+    DELIVERED_DRAG_END.with(|d| match d.get() {
+        Some((i, accepted)) if i == id => {
+            d.set(None);
+            Some(accepted)
+        }
+        _ => None,
+    })
+}
+
+/// Drops whatever the widgets didn't take
+pub(crate) fn clear_delivered() {
+    DELIVERED_DROP.with(|d| d.replace(None));
+    DELIVERED_DRAG_END.with(|d| d.set(None));
+}
+
+/// Asks the surface currently processing events to start a drag, returns the id of the drag
+pub(crate) fn request_drag(data: DragData) -> u64 {
+    let id = NEXT_DRAG_ID.with(|n| {
+        let id = n.get();
+        n.set(id + 1);
+        id
+    });
+    DRAG_REQUEST.with(|r| r.replace(Some((id, data))));
+    id
+}
+
+pub(crate) fn take_drag_request() -> Option<(u64, DragData)> {
+    DRAG_REQUEST.with(|r| r.take())
+}
+
+/// The mime type the target under the position wants from the offered ones
+fn target_mime_type(surface: &wl_surface::WlSurface, pos: Point, offered: &[String]) -> Option<String> {
+    SURFACES.with(|s| {
+        let s = s.borrow();
+        let reg = s.iter().find(|r| r.surface == *surface)?;
+        // the last drawn target is the topmost one
+        let (_, accepted) = reg.targets.iter().rev().find(|(b, _)| b.contains(pos))?;
+        accepted.iter().find(|m| offered.contains(m)).cloned()
+    })
+}
+
+fn update_offer(offer: Option<&data_device::DataOffer>, mime_type: Option<String>) {
+    if let Some(offer) = offer {
+        if mime_type.is_some() {
+            offer.set_actions(DndAction::Copy | DndAction::Move, DndAction::Copy);
+        } else {
+            offer.set_actions(DndAction::empty(), DndAction::empty());
+        }
+        offer.accept(mime_type);
+    }
+}
+
+fn offered_types(offer: Option<&data_device::DataOffer>) -> Vec<String> {
+    offer.map(|o| o.with_mime_types(|t| t.to_vec())).unwrap_or_default()
+}
+
+/// Reads the whole pipe without blocking the main loop
+async fn read_pipe(pipe: data_device::ReadPipe) -> Result<String, glib::Error> {
+    let stream = unsafe { gio::UnixInputStream::take_fd(pipe) };
+    let mut data = Vec::new();
+    loop {
+        let chunk = stream.read_bytes_future(4096, glib::PRIORITY_DEFAULT).await?;
+        if chunk.is_empty() {
+            break;
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// Tells the source that the last drop is done, which also destroys its offer
+fn finish_drop() {
+    if let Some((offer, surface)) = DROPPED.with(|d| d.take()) {
+        // SAFETY: SCTK keeps the offer of the last drag until the next one enters (which clears DROPPED),
+        // and the data device it's in lives as long as the environment, which the surface's instance holds
+        if !SURFACES.with(|s| s.borrow().iter().any(|r| r.surface == surface)) {
+            return;
+        }
+        let offer = unsafe { &*offer };
+        if !offer.get_current_action().is_empty() {
+            offer.finish();
+        }
+    }
+}
+
+/// The data device callback, set up along with the environment
+pub(crate) fn on_data_device_event(event: data_device::DndEvent) {
+    match event {
+        data_device::DndEvent::Enter {
+            offer, surface, x, y, ..
+        } => {
+            // the previous offer is gone now
+            DROPPED.with(|d| d.replace(None));
+            let pos = Point::new(x as _, y as _);
+            let mime_type = target_mime_type(&surface, pos, &offered_types(offer));
+            update_offer(offer, mime_type);
+            send_to(&surface, DndEvent::Enter(pos));
+            CURRENT.with(|c| c.replace(Some((surface, pos))));
+        }
+        data_device::DndEvent::Motion { offer, x, y, .. } => {
+            let pos = Point::new(x as _, y as _);
+            let surface = match CURRENT.with(|c| c.borrow().as_ref().map(|(s, _)| s.clone())) {
+                Some(s) => s,
+                None => return,
+            };
+            let mime_type = target_mime_type(&surface, pos, &offered_types(offer));
+            update_offer(offer, mime_type);
+            send_to(&surface, DndEvent::Motion(pos));
+            CURRENT.with(|c| c.replace(Some((surface, pos))));
+        }
+        data_device::DndEvent::Leave => {
+            if let Some((surface, _)) = CURRENT.with(|c| c.take()) {
+                send_to(&surface, DndEvent::Leave);
+            }
+        }
+        data_device::DndEvent::Drop { offer } => {
+            let (surface, position) = match CURRENT.with(|c| c.take()) {
+                Some(c) => c,
+                None => return,
+            };
+            let offer = match offer {
+                Some(o) => o,
+                None => return,
+            };
+            if let Some(mime_type) = target_mime_type(&surface, position, &offered_types(Some(offer))) {
+                match offer.receive(mime_type.clone()) {
+                    Ok(pipe) => {
+                        // finished (and destroyed) only once the data is in, the source might not send it otherwise
+                        DROPPED.with(|d| d.replace(Some((offer as *const _, surface.clone()))));
+                        let surface = surface.clone();
+                        glib::MainContext::default().spawn_local(async move {
+                            match read_pipe(pipe).await {
+                                Ok(data) => send_to(
+                                    &surface,
+                                    DndEvent::Drop(DropData {
+                                        mime_type,
+                                        data,
+                                        position,
+                                    }),
+                                ),
+                                Err(e) => eprintln!("Could not read dropped data: {:?}", e),
+                            }
+                            finish_drop();
+                        });
+                    }
+                    Err(e) => eprintln!("Could not receive dropped data: {:?}", e),
+                }
+            }
+            send_to(&surface, DndEvent::Leave);
+        }
+    }
+}
+
+/// The surface rendering the icon under the cursor during a drag
+pub(crate) struct DragIcon {
+    wl_surface: Attached<wl_surface::WlSurface>,
+    // dropped before the surface
+    gpu: Option<(Rc<Gpu>, iced_wgpu::Renderer, wgpu::Surface)>,
+}
+
+impl Drop for DragIcon {
+    fn drop(&mut self) {
+        drop(self.gpu.take());
+        self.wl_surface.destroy();
+    }
+}
+
+/// Starts a drag from `origin` and returns the icon, which has to be kept alive until the drag ends.
+/// `serial` has to be the serial of the button press that started the drag,
+/// the icon is drawn with the GPU device of the origin surface.
+pub(crate) fn start_drag(
+    gpu: &Rc<Gpu>,
+    env: &Environment<Env>,
+    display: &Display,
+    seat: &wl_seat::WlSeat,
+    origin: &wl_surface::WlSurface,
+    serial: u32,
+    id: u64,
+    data: DragData,
+) -> Option<DragIcon> {
+    let source_surface = origin.clone();
+    let offers = data.offers.clone();
+    let source = env.new_data_source(
+        offers.iter().map(|(m, _)| m.clone()).collect::<Vec<_>>(),
+        move |event, _| match event {
+            data_device::DataSourceEvent::Send { mime_type, mut pipe } => {
+                if let Some((_, contents)) = offers.iter().find(|(m, _)| *m == mime_type) {
+                    if let Err(x) = write!(pipe, "{}", contents) {
+                        eprintln!("Could not send dragged data: {:?}", x);
+                    }
+                }
+            }
+            data_device::DataSourceEvent::Finished => {
+                send_to(&source_surface, DndEvent::DragEnd { id, accepted: true })
+            }
+            data_device::DataSourceEvent::Cancelled => {
+                send_to(&source_surface, DndEvent::DragEnd { id, accepted: false })
+            }
+            _ => (),
+        },
+    );
+
+    let icon = data.icon.as_ref().map(|_| DragIcon {
+        wl_surface: env.create_surface(),
+        gpu: None,
+    });
+    let started = env.with_data_device(seat, |device| {
+        device.start_drag(
+            origin,
+            Some(source),
+            DndAction::Copy | DndAction::Move,
+            icon.as_ref().map(|i| &*i.wl_surface),
+            serial,
+        );
+    });
+    if started.is_err() {
+        eprintln!("No data device, can't start a drag");
+        return None;
+    }
+
+    let mut icon = icon?;
+    let size = data.icon_size as u32;
+    let rwh = ToRWH((*icon.wl_surface.as_ref()).clone(), (**display).clone());
+    let gpu = gpu.clone();
+    let gpu_surface = gpu.create_surface(&rwh);
+    let mut renderer = gpu.create_renderer();
+    gpu.configure_surface(&gpu_surface, size, size);
+    let element: Element<'_, (), iced_wgpu::Renderer> = match data.icon.clone().unwrap() {
+        ImageHandle::Raster(h) => {
+            renderer.backend_mut().retain_raster(&h);
+            iced_native::widget::Image::new(h)
+                .width(Length::Units(data.icon_size))
+                .height(Length::Units(data.icon_size))
+                .into()
+        }
+        ImageHandle::Vector(h) => {
+            renderer.backend_mut().retain_vector(&h);
+            iced_native::widget::Svg::new(h)
+                .width(Length::Units(data.icon_size))
+                .height(Length::Units(data.icon_size))
+                .into()
+        }
+    };
+    let mut ui = UserInterface::build(element, Size::new(size as _, size as _), Cache::new(), &mut renderer);
+    ui.draw(&mut renderer, Point::new(-1.0, -1.0));
+    drop(ui);
+    let viewport = iced_graphics::Viewport::with_physical_size(iced_graphics::Size::new(size, size), 1.0);
+    if let Err(e) = gpu.present(&mut renderer, &gpu_surface, &viewport) {
+        eprintln!("Could not draw the drag icon: {:?}", e);
+    }
+    icon.gpu = Some((gpu, renderer, gpu_surface));
+    Some(icon)
+}
@@ -1,6 +1,6 @@
 use futures::{
     channel::{mpsc, oneshot},
-    future::FusedFuture,
+    future::{self, FusedFuture},
     prelude::*,
     task,
 };
@@ -11,29 +11,165 @@ use smithay_client_toolkit::{
     },
     seat,
 };
-use std::{marker::Unpin, pin::Pin};
+use std::{
+    cell::{Cell, RefCell},
+    marker::Unpin,
+    pin::Pin,
+    rc::Rc,
+    time::Duration,
+};
+
+use crate::surfaces::{make_env, Display, Env, Environment};
+
+thread_local! {
+    static CONNECTION_LOST: (Cell<bool>, event_listener::Event) = (Cell::new(false), event_listener::Event::new());
+}
 
-/// Enables Wayland event dispatch on the glib event loop. Requires 'static :(
-pub fn glib_add_wayland(event_queue: &'static mut EventQueue) {
+/// Whether the compositor connection of the current main loop has been lost.
+pub fn is_connection_lost() -> bool {
+    CONNECTION_LOST.with(|(lost, _)| lost.get())
+}
+
+/// Resolves once the compositor connection is lost, so that surfaces can tear themselves down.
+pub fn connection_lost() -> impl FusedFuture<Output = ()> {
+    CONNECTION_LOST.with(|(lost, notifier)| {
+        let listener = notifier.listen();
+        // check after listening to not miss a notification in between
+        if lost.get() {
+            future::Either::Left(future::ready(()))
+        } else {
+            future::Either::Right(listener)
+        }
+        .fuse()
+    })
+}
+
+fn mark_connection_lost(display: &Display) {
+    if let Some(err) = display.protocol_error() {
+        eprintln!("Wayland protocol error: {}", err);
+    }
+    eprintln!("Lost connection to the compositor");
+    CONNECTION_LOST.with(|(lost, notifier)| {
+        lost.set(true);
+        notifier.notify(usize::MAX);
+    });
+}
+
+/// Enables Wayland event dispatch on the glib event loop, the queue is dropped along with the source.
+/// `on_lost` is called (once) when the connection to the compositor breaks.
+pub fn glib_add_wayland(mut event_queue: EventQueue, on_lost: impl FnOnce() + 'static) {
+    CONNECTION_LOST.with(|(lost, _)| lost.set(false));
     let fd = event_queue.display().get_connection_fd();
-    glib::source::unix_fd_add_local(fd, glib::IOCondition::IN, move |_fd, _ioc| {
-        if let Some(guard) = event_queue.prepare_read() {
-            if let Err(e) = event_queue.display().flush() {
-                eprintln!("Error flushing the wayland socket: {:?}", e);
-            }
+    let mut on_lost = Some(on_lost);
+    glib::source::unix_fd_add_local(
+        fd,
+        glib::IOCondition::IN | glib::IOCondition::HUP | glib::IOCondition::ERR,
+        move |_fd, ioc| {
+            let mut alive = !ioc.intersects(glib::IOCondition::HUP | glib::IOCondition::ERR);
+            if let Some(guard) = event_queue.prepare_read() {
+                if let Err(e) = event_queue.display().flush() {
+                    if e.kind() != std::io::ErrorKind::WouldBlock {
+                        eprintln!("Error flushing the wayland socket: {:?}", e);
+                        alive = false;
+                    }
+                }
 
-            if let Err(e) = guard.read_events() {
-                if e.kind() == std::io::ErrorKind::WouldBlock {
-                    eprintln!("Reading from the wayland socket would block!");
-                    return glib::Continue(true);
-                } else {
-                    eprintln!("Error reading from the wayland socket: {:?}", e);
+                if let Err(e) = guard.read_events() {
+                    if e.kind() == std::io::ErrorKind::WouldBlock {
+                        eprintln!("Reading from the wayland socket would block!");
+                        return glib::Continue(true);
+                    } else {
+                        eprintln!("Error reading from the wayland socket: {:?}", e);
+                        alive = false;
+                    }
+                }
+            }
+            if let Err(e) = event_queue.dispatch_pending(&mut (), |_, _, _| {}) {
+                eprintln!("Error dispatching wayland events: {:?}", e);
+                alive = false;
+            }
+            if !alive {
+                mark_connection_lost(event_queue.display());
+                if let Some(f) = on_lost.take() {
+                    f();
                 }
             }
+            glib::Continue(alive)
+        },
+    );
+}
+
+/// What to do when the compositor goes away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnDisconnect {
+    /// Quit with a `ConnectionLost` error.
+    Exit,
+    /// Wait for a compositor to show up on `WAYLAND_DISPLAY` again, and start over.
+    Reconnect,
+}
+
+#[derive(Debug)]
+pub struct ConnectionLost;
+
+impl std::fmt::Display for ConnectionLost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "lost connection to the Wayland compositor")
+    }
+}
+
+impl std::error::Error for ConnectionLost {}
+
+/// Runs the glib main loop with a Wayland connection, spawning `fun` on it.
+/// When the connection is lost, the `fun` future (with all of its surfaces, and the connection itself) is dropped.
+/// Anything that should survive a reconnect has to live outside of it.
+pub fn run_main<F, Fut>(fun: F, on_disconnect: OnDisconnect) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: Fn(Environment<Env>, Display) -> Fut,
+    Fut: Future<Output = ()> + 'static,
+{
+    let main = glib::MainLoop::new(None, false);
+    let _main_guard = glib::MainContext::default().acquire()?;
+    crate::theme::load_and_watch();
+    glib::MainContext::default().spawn_local(crate::settings::watch());
+    let mut conn = Some(make_env()?);
+    loop {
+        let (env, disp, queue) = conn.take().unwrap();
+        let (fut, abort) = future::abortable(fun(env, disp));
+        glib_add_wayland(queue, move || abort.abort());
+        {
+            let main = main.clone();
+            glib::MainContext::default().spawn_local(async move {
+                // the inner future is dropped when this await completes, before quitting
+                let _ = fut.await;
+                main.quit();
+            });
         }
-        event_queue.dispatch_pending(&mut (), |_, _, _| {}).unwrap();
-        glib::Continue(true)
-    });
+        main.run();
+
+        if !is_connection_lost() {
+            return Ok(());
+        }
+        if on_disconnect == OnDisconnect::Exit {
+            return Err(Box::new(ConnectionLost));
+        }
+
+        eprintln!("Waiting for the compositor to come back");
+        let reconnected = Rc::new(RefCell::new(None));
+        {
+            let main = main.clone();
+            let reconnected = reconnected.clone();
+            glib::source::timeout_add_local(Duration::from_millis(500), move || match make_env() {
+                Ok(c) => {
+                    reconnected.replace(Some(c));
+                    main.quit();
+                    glib::Continue(false)
+                }
+                Err(_) => glib::Continue(true),
+            });
+        }
+        main.run();
+        conn = reconnected.take();
+    }
 }
 
 /// Wayland proxy wrapper that provides an async channel for the object's events,
@@ -147,6 +283,23 @@ where
     rx
 }
 
+/// Creates a mpsc channel for any Wayland object's events, for waiting on a sequence of them.
+pub fn wayland_event_chan<I>(obj: &Main<I>) -> mpsc::UnboundedReceiver<I::Event>
+where
+    I: Interface + AsRef<Proxy<I>> + From<Proxy<I>> + Sync,
+    I::Event: MessageGroup<Map = ProxyMap>,
+{
+    let (tx, rx) = mpsc::unbounded();
+    obj.quick_assign(move |_, event, _| {
+        if let Err(e) = tx.unbounded_send(event) {
+            if !e.is_disconnected() {
+                panic!("Unexpected send error {:?}", e)
+            }
+        }
+    });
+    rx
+}
+
 /// Creates a mpsc channel for a Wayland object's events.
 pub fn wayland_keyboard_chan(
     seat: &Attached<wl_seat::WlSeat>,
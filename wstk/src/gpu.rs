@@ -0,0 +1,162 @@
+//! The wgpu device, owned here instead of by iced's compositor (which keeps it private)
+//! so that device errors can be noticed and the device replaced, and so that drag icons
+//! can draw with the device of the surface they're dragged from.
+
+use crate::{handle::ToRWH, typography};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// How long to wait before asking for a device again when there isn't one (e.g. while the GPU is being reset)
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Staging buffer chunk size, same as iced's compositor
+const CHUNK_SIZE: u64 = 10 * 1024;
+
+pub struct Gpu {
+    settings: iced_wgpu::Settings,
+    instance: wgpu::Instance,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    format: wgpu::TextureFormat,
+    staging_belt: RefCell<wgpu::util::StagingBelt>,
+    lost: Arc<AtomicBool>,
+}
+
+impl Gpu {
+    /// Sets up a device that can draw onto the given surface, waiting for one as long as it takes.
+    pub async fn new(rwh: &ToRWH) -> (Rc<Gpu>, wgpu::Surface) {
+        loop {
+            match Gpu::request(rwh).await {
+                Ok((gpu, surface)) => return (Rc::new(gpu), surface),
+                Err(e) => eprintln!("Could not set up the GPU: {}, retrying", e),
+            }
+            glib::timeout_future(RETRY_DELAY).await;
+        }
+    }
+
+    async fn request(rwh: &ToRWH) -> Result<(Gpu, wgpu::Surface), String> {
+        let settings = iced_wgpu::Settings {
+            default_font: typography::default_font_bytes(),
+            default_text_size: typography::text_size(typography::TextSize::Body),
+            ..iced_wgpu::Settings::default()
+        };
+        let instance = wgpu::Instance::new(settings.internal_backend);
+        let surface = unsafe { instance.create_surface(rwh) };
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::LowPower,
+                compatible_surface: Some(&surface),
+                force_fallback_adapter: false,
+            })
+            .await
+            .ok_or("no adapter")?;
+        let format = surface.get_preferred_format(&adapter).ok_or("no surface format")?;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("wstk device"),
+                    features: wgpu::Features::empty(),
+                    limits: wgpu::Limits {
+                        max_bind_groups: 2,
+                        ..wgpu::Limits::default()
+                    },
+                },
+                None,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        // the default handler panics, instead the device gets replaced on the next frame
+        let lost = Arc::new(AtomicBool::new(false));
+        let lost_h = lost.clone();
+        device.on_uncaptured_error(move |e| {
+            eprintln!("GPU error: {}", e);
+            lost_h.store(true, Ordering::Relaxed);
+        });
+        let gpu = Gpu {
+            settings,
+            instance,
+            device,
+            queue,
+            format,
+            staging_belt: RefCell::new(wgpu::util::StagingBelt::new(CHUNK_SIZE)),
+            lost,
+        };
+        Ok((gpu, surface))
+    }
+
+    /// Whether the device had an error and has to be replaced with a new `Gpu`
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    pub fn create_renderer(&self) -> iced_wgpu::Renderer {
+        iced_wgpu::Renderer::new(iced_wgpu::Backend::new(&self.device, self.settings, self.format))
+    }
+
+    pub fn create_surface(&self, rwh: &ToRWH) -> wgpu::Surface {
+        unsafe { self.instance.create_surface(rwh) }
+    }
+
+    pub fn configure_surface(&self, surface: &wgpu::Surface, width: u32, height: u32) {
+        surface.configure(
+            &self.device,
+            &wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                format: self.format,
+                present_mode: self.settings.present_mode,
+                width,
+                height,
+            },
+        );
+    }
+
+    /// Draws what's in the renderer onto a transparent frame of the surface.
+    pub fn present(
+        &self,
+        renderer: &mut iced_wgpu::Renderer,
+        surface: &wgpu::Surface,
+        viewport: &iced_graphics::Viewport,
+    ) -> Result<(), wgpu::SurfaceError> {
+        let frame = surface.get_current_texture()?;
+        let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("wstk encoder"),
+        });
+        let _ = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("wstk clear pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        let mut staging_belt = self.staging_belt.borrow_mut();
+        renderer.with_primitives(|backend, primitives| {
+            backend.present::<String>(
+                &self.device,
+                &mut staging_belt,
+                &mut encoder,
+                &view,
+                primitives,
+                viewport,
+                &[],
+            );
+        });
+        staging_belt.finish();
+        self.queue.submit(Some(encoder.finish()));
+        frame.present();
+        glib::MainContext::default().spawn_local(staging_belt.recall());
+        Ok(())
+    }
+}
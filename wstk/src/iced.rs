@@ -1,20 +1,25 @@
-use iced_graphics::window::Compositor;
 pub use iced_native::Rectangle;
 use iced_native::{keyboard, mouse, user_interface::Cache, Damage, Point, Renderer, Size, UserInterface};
-use iced_wgpu::window::Compositor as WgpuCompositor;
 
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     io::{Read, Write},
     pin::Pin,
+    rc::Rc,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 pub use async_trait::async_trait;
 pub use futures::{channel::mpsc, future, prelude::*};
 
-use crate::{event_loop::*, run::*, surfaces::*};
+use crate::{
+    a11y, dnd, event_loop::*, gpu::Gpu, handle::ToRWH, motion, run::*, subscription::*, surfaces::*,
+    theme::theme_changed, typography, widgets,
+};
+
+/// How soon to try drawing again after the GPU could not give us a frame
+const FRAME_RETRY_DELAY: Duration = Duration::from_millis(100);
 
 pub struct Clipboard {
     env: Environment<Env>,
@@ -85,6 +90,37 @@ impl iced_native::Clipboard for Clipboard {
     }
 }
 
+thread_local! {
+    static REDRAW_REQUEST: Cell<Option<Instant>> = Cell::new(None);
+}
+
+/// For widgets that change over time (e.g. show up after a delay):
+/// makes the surface currently being updated or drawn process events and draw again at the given time.
+pub fn request_redraw_at(at: Instant) {
+    REDRAW_REQUEST.with(|r| {
+        if r.get().map(|cur| at < cur).unwrap_or(true) {
+            r.set(Some(at));
+        }
+    });
+}
+
+/// Lets the widgets that report things while being drawn (input regions, drag and drop, focus, accessibility)
+/// collect them, finished in `IcedInstance::finish_frame`
+fn start_collecting() {
+    widgets::inputregion::start_collecting();
+    dnd::start_collecting();
+    widgets::focusable::start_collecting();
+    a11y::start_collecting();
+}
+
+fn take_redraw_request(into: &mut Option<Instant>) {
+    if let Some(at) = REDRAW_REQUEST.with(|r| r.take()) {
+        if into.map(|cur| at < cur).unwrap_or(true) {
+            *into = Some(at);
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum Action {
     DoNothing,
@@ -102,13 +138,34 @@ pub type Element<'a, Message> = iced_native::Element<'a, Message, iced_wgpu::Ren
 
 #[async_trait(?Send)]
 pub trait IcedSurface {
-    type Message: std::fmt::Debug + Send;
+    type Message: std::fmt::Debug + Send + 'static;
 
     fn view(&mut self) -> Element<'_, Self::Message>;
     fn input_region(&self, _width: u32, _height: u32) -> Option<Vec<Rectangle<u32>>> {
         None
     }
+    /// Whether the input region is made of the `InputRegion` widgets in the view.
+    /// Rectangles from `input_region` are added on top, everything else is click-through.
+    fn auto_input_region(&self) -> bool {
+        false
+    }
     fn retained_images(&mut self) -> Vec<ImageHandle>;
+    /// Keyboard interactivity to switch the layer surface to when it changes,
+    /// e.g. `OnDemand` only while the surface is shown. None leaves what `setup_lsh` set.
+    fn keyboard_interactivity(&self) -> Option<layer_surface::KeyboardInteractivity> {
+        None
+    }
+    /// Timers and streams producing messages, see `Subscription`
+    fn subscriptions(&self) -> Vec<Subscription<Self::Message>> {
+        Vec::new()
+    }
+    /// Name of the window for assistive technologies
+    fn accessible_name(&self) -> String {
+        String::new()
+    }
+    /// Called after each layout and draw, e.g. to tell the compositor where things ended up
+    /// (with bounds tracked by `GetRegion` widgets)
+    fn after_draw(&mut self, _surface: &wl_surface::WlSurface) {}
 
     async fn update(&mut self, message: Self::Message);
     async fn run(&mut self) -> Action;
@@ -117,6 +174,8 @@ pub trait IcedSurface {
     async fn on_pointer_leave(&mut self) {}
     async fn on_touch_enter(&mut self) {}
     async fn on_touch_leave(&mut self) {}
+    async fn on_keyboard_enter(&mut self) {}
+    async fn on_keyboard_leave(&mut self) {}
 }
 
 pub struct IcedInstance<T: IcedSurface> {
@@ -128,11 +187,20 @@ pub struct IcedInstance<T: IcedSurface> {
     kb_active: bool,
     scale: i32,
     leave_timeout: Option<future::Fuse<Pin<Box<dyn Future<Output = ()> + Send + 'static>>>>,
+    redraw_at: Option<Instant>,
+    redraw_timeout: Option<future::Fuse<Pin<Box<dyn Future<Output = ()> + Send + 'static>>>>,
     prev_input_region: Option<Vec<Rectangle<u32>>>,
+    prev_keyboard_interactivity: Option<layer_surface::KeyboardInteractivity>,
+    auto_input_region: Vec<Rectangle<u32>>,
     touch_point: Option<i32>,
     touch_leave: bool,
     themed_ptr: Option<pointer::ThemedPointer>,
     last_ptr_serial: Option<u32>,
+    last_press_serial: u32,
+    dnd_events: mpsc::UnboundedReceiver<dnd::DndEvent>,
+    drag_icon: Option<dnd::DragIcon>,
+    a11y_window: u32,
+    a11y_requests: mpsc::UnboundedReceiver<a11y::Request>,
     keyboard_handle: Option<Main<wl_keyboard::WlKeyboard>>,
     keyboard_events: mpsc::UnboundedReceiver<seat::keyboard::Event>,
     ptr: Option<AsyncMain<wl_pointer::WlPointer>>,
@@ -144,13 +212,18 @@ pub struct IcedInstance<T: IcedSurface> {
     size: Size,
     cursor_position: Point,
     keyboard_mods: keyboard::Modifiers,
+    focused: Option<Rectangle>,
+    focus_changed: bool,
+    focus_chain: Vec<Rectangle>,
     paste_inject_rx: mpsc::UnboundedReceiver<()>,
-    compositor: WgpuCompositor,
-    renderer: <WgpuCompositor as Compositor>::Renderer,
-    gpu_surface: <WgpuCompositor as Compositor>::Surface,
+    gpu: Rc<Gpu>,
+    renderer: iced_wgpu::Renderer,
+    gpu_surface: wgpu::Surface,
+    gpu_text: (Option<String>, u16),
     prev_prim: iced_graphics::Primitive,
     queue: Vec<iced_native::Event>,
     messages: Vec<T::Message>,
+    subscriptions: Subscriptions<T::Message>,
     last_mouse_interaction: mouse::Interaction,
     clipboard: Clipboard,
 }
@@ -163,18 +236,8 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
         output: wl_output::WlOutput,
     ) -> IcedInstance<T> {
         let parent = DesktopInstance::new(&surface, env.clone(), display, &output);
-        let rwh = parent.raw_handle();
-
-        let mut compositor = WgpuCompositor::request(
-            iced_wgpu::Settings {
-                ..iced_wgpu::Settings::default()
-            },
-            Some(&rwh),
-        )
-        .await
-        .unwrap();
-        let renderer = iced_wgpu::Renderer::new(compositor.create_backend());
-        let gpu_surface = compositor.create_surface(&rwh);
+        let (gpu, gpu_surface) = Gpu::new(&parent.raw_handle()).await;
+        let renderer = gpu.create_renderer();
         parent.wl_surface.commit();
         parent.flush();
 
@@ -200,6 +263,10 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
         };
 
         let (paste_inject_tx, paste_inject_rx) = futures::channel::mpsc::unbounded();
+        let dnd_events = dnd::register(parent.wl_surface.detach());
+        let (a11y_window, a11y_requests) = a11y::register_window(surface.accessible_name());
+        let mut subscriptions = Subscriptions::new();
+        subscriptions.update(surface.subscriptions());
 
         IcedInstance {
             parent,
@@ -208,11 +275,20 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
             kb_active: false,
             scale: 1,
             leave_timeout: None,
+            redraw_at: None,
+            redraw_timeout: None,
             prev_input_region: None,
+            prev_keyboard_interactivity: None,
+            auto_input_region: Vec::new(),
             touch_point: None,
             touch_leave: false,
             themed_ptr,
             last_ptr_serial: None,
+            last_press_serial: 0,
+            dnd_events,
+            drag_icon: None,
+            a11y_window,
+            a11y_requests,
             keyboard_handle,
             keyboard_events,
             ptr,
@@ -222,12 +298,17 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
             size: Size::new(0.0, 0.0),
             cursor_position: Point::default(),
             keyboard_mods: Default::default(),
-            compositor,
+            focused: None,
+            focus_changed: false,
+            focus_chain: Vec::new(),
+            gpu,
             renderer,
             gpu_surface,
+            gpu_text: gpu_text_settings(),
             prev_prim: iced_graphics::Primitive::None,
             queue: Vec::new(),
             messages: Vec::new(),
+            subscriptions,
             last_mouse_interaction: mouse::Interaction::Idle,
             clipboard: Clipboard {
                 env,
@@ -241,7 +322,12 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
     }
 
     fn update_input_region(&mut self) {
-        let reg = self.surface.input_region(self.size.width as _, self.size.height as _);
+        let mut reg = self.surface.input_region(self.size.width as _, self.size.height as _);
+        if self.surface.auto_input_region() {
+            let mut rects = reg.unwrap_or_default();
+            rects.extend(self.auto_input_region.iter().cloned());
+            reg = Some(rects);
+        }
         if reg != self.prev_input_region {
             if let Some(ref rects) = reg {
                 let wlreg = self.parent.create_region();
@@ -262,6 +348,41 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
         self.prev_input_region = reg;
     }
 
+    fn update_keyboard_interactivity(&mut self) {
+        use layer_surface::KeyboardInteractivity;
+        let mut interactivity = self.surface.keyboard_interactivity();
+        if interactivity == self.prev_keyboard_interactivity {
+            return;
+        }
+        self.prev_keyboard_interactivity = interactivity;
+        if interactivity == Some(KeyboardInteractivity::OnDemand) && self.parent.layer_surface.as_ref().version() < 4 {
+            eprintln!("Compositor does not support on-demand keyboard interactivity");
+            interactivity = Some(KeyboardInteractivity::None);
+        }
+        if let Some(interactivity) = interactivity {
+            self.parent.layer_surface.set_keyboard_interactivity(interactivity);
+            self.parent.wl_surface.commit();
+        }
+    }
+
+    /// Tab/Shift+Tab
+    fn move_focus(&mut self, backwards: bool) {
+        let chain = &self.focus_chain;
+        if chain.is_empty() {
+            self.focused = None;
+            return;
+        }
+        let cur = self.focused.and_then(|f| chain.iter().position(|b| *b == f));
+        let next = match (cur, backwards) {
+            (None, false) => 0,
+            (None, true) => chain.len() - 1,
+            (Some(i), false) => (i + 1) % chain.len(),
+            (Some(i), true) => (i + chain.len() - 1) % chain.len(),
+        };
+        self.focused = Some(chain[next]);
+        self.focus_changed = true;
+    }
+
     fn apply_mouse_interaction(&mut self, interaction: mouse::Interaction) {
         if let Some(ref tptr) = self.themed_ptr {
             use iced_native::mouse::Interaction::*;
@@ -284,8 +405,46 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
     }
 
     async fn render(&mut self) {
+        let prev_redraw_at = self.redraw_at;
+        if !self.render_frame().await {
+            // the renderer was recreated and lost the frame, draw it (and upload retained images) again
+            self.render_frame().await;
+        }
+        if let Some(next) = widgets::focusable::take_request() {
+            self.focused = Some(next);
+            self.focus_changed = true;
+            // let the newly focused widget see an event
+            self.queue.push(iced_native::Event::Mouse(mouse::Event::CursorMoved {
+                position: self.cursor_position,
+            }));
+            self.render_frame().await;
+        }
+        self.update_keyboard_interactivity();
+        if let Some((id, data)) = dnd::take_drag_request() {
+            self.drag_icon = dnd::start_drag(
+                &self.gpu,
+                &self.parent.env,
+                &self.parent.display,
+                &self.clipboard.seat,
+                &self.parent.wl_surface.detach(),
+                self.last_press_serial,
+                id,
+                data,
+            );
+            self.parent.flush();
+        }
+        if let Some(at) = self.redraw_at {
+            if prev_redraw_at != Some(at) {
+                let timeout = at.saturating_duration_since(Instant::now());
+                self.redraw_timeout = Some(glib::timeout_future(timeout).fuse());
+            }
+        }
+    }
+
+    /// Returns false if the GPU device had to be recreated while presenting.
+    async fn render_frame(&mut self) -> bool {
         if !self.configured {
-            return;
+            return true;
         }
 
         for h in self.surface.retained_images() {
@@ -295,6 +454,8 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
             }
         }
 
+        widgets::focusable::set_focused(self.focused, self.focus_changed);
+        self.focus_changed = false;
         let mut user_interface =
             UserInterface::build(self.surface.view(), self.size, self.cache.clone(), &mut self.renderer);
         user_interface.update(
@@ -304,6 +465,7 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
             &mut self.clipboard,
             &mut self.messages,
         );
+        take_redraw_request(&mut self.redraw_at);
         let viewport = iced_graphics::Viewport::with_physical_size(
             iced_graphics::Size::new(
                 self.size.width as u32 * self.scale as u32,
@@ -313,26 +475,10 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
         );
 
         if self.messages.is_empty() {
+            start_collecting();
             let mi = user_interface.draw(&mut self.renderer, self.cursor_position);
-            let next_prim = self.renderer.damage_token();
-            let dmg = self.prev_prim.damage(&next_prim);
-            self.prev_prim = next_prim;
-            if dmg == None || dmg.map(|x| x.len()).unwrap_or(0) == 0 {
-                self.cache = user_interface.into_cache();
-                self.update_input_region();
-                return;
-            }
-            self.compositor
-                .present::<String>(
-                    &mut self.renderer,
-                    &mut self.gpu_surface,
-                    &viewport,
-                    iced_core::Color::TRANSPARENT,
-                    &[],
-                )
-                .unwrap();
             self.cache = user_interface.into_cache();
-            self.apply_mouse_interaction(mi);
+            self.finish_frame(mi, &viewport).await
         } else {
             // iced-winit says we are forced to rebuild twice
             let temp_cache = user_interface.into_cache();
@@ -340,36 +486,108 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
             for message in self.messages.drain(..) {
                 self.surface.update(message).await;
             }
+            self.subscriptions.update(self.surface.subscriptions());
             self.parent.flush();
 
             let mut user_interface =
                 UserInterface::build(self.surface.view(), self.size, temp_cache, &mut self.renderer);
+            start_collecting();
             let mi = user_interface.draw(&mut self.renderer, self.cursor_position);
-            let next_prim = self.renderer.damage_token();
-            let dmg = self.prev_prim.damage(&next_prim);
-            self.prev_prim = next_prim;
-            if dmg == None || dmg.map(|x| x.len()).unwrap_or(0) == 0 {
-                self.cache = user_interface.into_cache();
-                return;
-            }
-            self.compositor
-                .present::<String>(
-                    &mut self.renderer,
-                    &mut self.gpu_surface,
-                    &viewport,
-                    iced_core::Color::TRANSPARENT,
-                    &[],
-                )
-                .unwrap();
             self.cache = user_interface.into_cache();
+            self.finish_frame(mi, &viewport).await
+        }
+    }
+
+    /// Takes what the widgets collected while drawing (see `start_collecting`) and presents the frame if it changed.
+    /// Returns false if the GPU device had to be recreated while presenting.
+    async fn finish_frame(&mut self, mi: mouse::Interaction, viewport: &iced_graphics::Viewport) -> bool {
+        self.auto_input_region = widgets::inputregion::finish_collecting();
+        dnd::finish_collecting(&self.parent.wl_surface);
+        self.focus_chain = widgets::focusable::finish_collecting();
+        a11y::finish_collecting(self.a11y_window, self.kb_active);
+        if !self.focused.map(|f| self.focus_chain.contains(&f)).unwrap_or(true) {
+            // the focused widget went away
+            self.focused = None;
+        }
+        take_redraw_request(&mut self.redraw_at);
+        self.surface.after_draw(&self.parent.wl_surface);
+        let next_prim = self.renderer.damage_token();
+        let dmg = self.prev_prim.damage(&next_prim);
+        self.prev_prim = next_prim;
+        if dmg.map(|x| x.len()).unwrap_or(0) != 0 {
+            if !self.present(viewport).await {
+                return false;
+            }
             self.apply_mouse_interaction(mi);
         }
         self.update_input_region();
+        true
+    }
+
+    /// Presents what's in the renderer, recovering from swapchain problems (e.g. after suspend/resume).
+    /// Returns false if the GPU device had to be recreated, which means the frame is gone.
+    async fn present(&mut self, viewport: &iced_graphics::Viewport) -> bool {
+        use wgpu::SurfaceError;
+        for _ in 0..2 {
+            if self.gpu.is_lost() {
+                break;
+            }
+            match self.gpu.present(&mut self.renderer, &self.gpu_surface, viewport) {
+                Ok(()) => return true,
+                Err(SurfaceError::Timeout) => {
+                    eprintln!("Timed out getting the next frame, trying again later");
+                    self.retry_frame();
+                    return true;
+                }
+                Err(SurfaceError::OutOfMemory) => {
+                    eprintln!("Out of memory getting the next frame, trying again later");
+                    self.retry_frame();
+                    return true;
+                }
+                Err(SurfaceError::Outdated) => {
+                    eprintln!("GPU surface outdated, reconfiguring");
+                    self.configure_surface();
+                }
+                Err(SurfaceError::Lost) => {
+                    eprintln!("GPU surface lost, recreating");
+                    self.gpu_surface = self.gpu.create_surface(&self.parent.raw_handle());
+                    self.configure_surface();
+                }
+            }
+        }
+        eprintln!("Could not present, recreating the GPU device");
+        self.recreate_gpu().await;
+        false
+    }
+
+    /// Makes the skipped frame get drawn in full a bit later
+    fn retry_frame(&mut self) {
+        self.prev_prim = iced_graphics::Primitive::None; // force damage next time
+        request_redraw_at(Instant::now() + FRAME_RETRY_DELAY);
+        take_redraw_request(&mut self.redraw_at);
+    }
+
+    async fn recreate_gpu(&mut self) {
+        self.gpu_text = gpu_text_settings();
+        let (gpu, gpu_surface) = Gpu::new(&self.parent.raw_handle()).await;
+        self.gpu_surface = gpu_surface;
+        self.renderer = gpu.create_renderer();
+        self.gpu = gpu;
+        self.cache = Cache::new();
+        self.configure_surface();
+    }
+
+    async fn on_theme_changed(&mut self) {
+        if gpu_text_settings() != self.gpu_text {
+            // the default font and text size can't be changed on a live renderer
+            self.recreate_gpu().await;
+        }
+        self.render().await;
     }
 
     fn configure_surface(&mut self) {
-        self.compositor.configure_surface(
-            &mut self.gpu_surface,
+        self.gpu.configure_surface(
+            &self.gpu_surface,
             self.size.width as u32 * self.scale as u32,
             self.size.height as u32 * self.scale as u32,
         );
@@ -418,12 +636,17 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
                 }
                 self.kb_active = true;
                 self.clipboard.last_enter_serial = serial;
+                self.surface.on_keyboard_enter().await;
+                self.render().await;
             }
             seat::keyboard::Event::Leave { surface, .. } => {
                 if self.parent.wl_surface.detach() != surface {
                     return;
                 }
-                self.kb_active = true;
+                self.kb_active = false;
+                self.focused = None;
+                self.surface.on_keyboard_leave().await;
+                self.render().await;
             }
             seat::keyboard::Event::Modifiers { modifiers, .. } => {
                 if !self.kb_active {
@@ -450,6 +673,8 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
                     .push(iced_native::Event::Keyboard(keyboard::Event::ModifiersChanged(
                         self.keyboard_mods,
                     )));
+                // deliver right away, e.g. releasing Alt can finish a window switch
+                self.render().await;
             }
             seat::keyboard::Event::Key {
                 keysym, state, utf8, ..
@@ -457,6 +682,14 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
                 if !self.kb_active {
                     return;
                 }
+                if state == seat::keyboard::KeyState::Pressed
+                    && (keysym == seat::keyboard::keysyms::XKB_KEY_Tab
+                        || keysym == seat::keyboard::keysyms::XKB_KEY_ISO_Left_Tab)
+                {
+                    self.move_focus(
+                        self.keyboard_mods.shift() || keysym == seat::keyboard::keysyms::XKB_KEY_ISO_Left_Tab,
+                    );
+                }
                 if let Some(key_code) = convert_key(keysym) {
                     self.queue.push(iced_native::Event::Keyboard(match state {
                         seat::keyboard::KeyState::Pressed => keyboard::Event::KeyPressed {
@@ -537,7 +770,7 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
                     return;
                 }
                 self.ptr_active = false;
-                self.leave_timeout = Some(glib::timeout_future(Duration::from_millis(200)).fuse());
+                self.leave_timeout = Some(glib::timeout_future(motion::duration(motion::POINTER_LEAVE_DELAY)).fuse());
                 self.last_ptr_serial = Some(serial);
             }
             wl_pointer::Event::Button {
@@ -559,6 +792,11 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
                     _ => panic!("new button state?"),
                 }));
                 self.last_ptr_serial = Some(serial);
+                if state == wl_pointer::ButtonState::Pressed {
+                    self.last_press_serial = serial;
+                    // the focus ring is for keyboard use
+                    self.focused = None;
+                }
             }
             wl_pointer::Event::Motion {
                 surface_x, surface_y, ..
@@ -607,6 +845,78 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
         }
     }
 
+    async fn on_dnd_event(&mut self, event: dnd::DndEvent) {
+        match event {
+            dnd::DndEvent::Enter(position) => {
+                // like a pointer for hover effects, but without buttons
+                self.leave_timeout = None;
+                self.surface.on_pointer_enter().await;
+                self.cursor_position = position;
+                self.queue
+                    .push(iced_native::Event::Mouse(mouse::Event::CursorMoved { position }));
+            }
+            dnd::DndEvent::Motion(position) => {
+                self.cursor_position = position;
+                self.queue
+                    .push(iced_native::Event::Mouse(mouse::Event::CursorMoved { position }));
+            }
+            dnd::DndEvent::Leave => {
+                if !self.ptr_active {
+                    self.leave_timeout =
+                        Some(glib::timeout_future(motion::duration(motion::POINTER_LEAVE_DELAY)).fuse());
+                }
+                return;
+            }
+            dnd::DndEvent::Drop(data) => {
+                self.cursor_position = data.position;
+                self.queue.push(iced_native::Event::Mouse(mouse::Event::CursorMoved {
+                    position: data.position,
+                }));
+                dnd::deliver_drop(data);
+            }
+            dnd::DndEvent::DragEnd { id, accepted } => {
+                self.drag_icon = None;
+                self.queue.push(iced_native::Event::Mouse(mouse::Event::CursorMoved {
+                    position: self.cursor_position,
+                }));
+                dnd::deliver_drag_end(id, accepted);
+            }
+        }
+        self.render().await;
+        dnd::clear_delivered();
+    }
+
+    async fn on_a11y_request(&mut self, request: a11y::Request) {
+        match request {
+            a11y::Request::Activate(bounds) => {
+                // a click in the middle of the widget, then back to where the pointer really is
+                self.queue.push(iced_native::Event::Mouse(mouse::Event::CursorMoved {
+                    position: bounds.center(),
+                }));
+                self.queue.push(iced_native::Event::Mouse(mouse::Event::ButtonPressed(
+                    mouse::Button::Left,
+                )));
+                self.queue.push(iced_native::Event::Mouse(mouse::Event::ButtonReleased(
+                    mouse::Button::Left,
+                )));
+                self.queue.push(iced_native::Event::Mouse(mouse::Event::CursorMoved {
+                    position: self.cursor_position,
+                }));
+            }
+            a11y::Request::Focus(bounds) => {
+                if !self.focus_chain.contains(&bounds) {
+                    return;
+                }
+                self.focused = Some(bounds);
+                self.focus_changed = true;
+                self.queue.push(iced_native::Event::Mouse(mouse::Event::CursorMoved {
+                    position: self.cursor_position,
+                }));
+            }
+        }
+        self.render().await;
+    }
+
     async fn on_touch_event(&mut self, event: wl_touch::Event) {
         match event {
             wl_touch::Event::Down { surface, id, x, y, .. } => {
@@ -660,6 +970,14 @@ impl<T: DesktopSurface + IcedSurface> IcedInstance<T> {
     }
 }
 
+/// The theme's parts that are baked into the renderer
+fn gpu_text_settings() -> (Option<String>, u16) {
+    (
+        crate::theme().fonts.interface.clone(),
+        typography::text_size(typography::TextSize::Body),
+    )
+}
+
 #[async_trait(?Send)]
 impl<T: DesktopSurface + IcedSurface> Runnable for IcedInstance<T> {
     async fn run(&mut self) -> bool {
@@ -667,6 +985,8 @@ impl<T: DesktopSurface + IcedSurface> Runnable for IcedInstance<T> {
         let this = self; // argh macro weirdness
         let mut term = future::Fuse::terminated();
         let mut leave_timeout = this.leave_timeout.as_mut().unwrap_or_else(|| &mut term);
+        let mut redraw_term = future::Fuse::terminated();
+        let mut redraw_timeout = this.redraw_timeout.as_mut().unwrap_or_else(|| &mut redraw_term);
         futures::select! {
             ev = this.parent.layer_surface.next() => if !this.on_layer_event(ev).await { return false },
             ev = this.keyboard_events.select_next_some() => this.on_keyboard_event(ev).await,
@@ -674,14 +994,32 @@ impl<T: DesktopSurface + IcedSurface> Runnable for IcedInstance<T> {
             ev = MaybeFuture::new(this.touch.as_mut().map(|p| p.next())) => this.on_touch_event(ev).await,
             sc = this.parent.scale_rx.select_next_some() => this.on_scale(sc).await,
             () = this.paste_inject_rx.select_next_some() => this.inject_paste().await,
+            ev = this.dnd_events.select_next_some() => this.on_dnd_event(ev).await,
+            rq = this.a11y_requests.select_next_some() => this.on_a11y_request(rq).await,
+            msg = this.subscriptions.next() => {
+                this.messages.push(msg);
+                this.render().await
+            },
             ac = this.surface.run().fuse() => match ac {
                 Action::DoNothing => (),
                 Action::Rerender => {
+                    this.subscriptions.update(this.surface.subscriptions());
                     this.parent.flush();
                     this.render().await
                 },
                 Action::Close => return false,
             },
+            () = connection_lost() => return false,
+            () = theme_changed() => this.on_theme_changed().await,
+            () = redraw_timeout => {
+                this.redraw_timeout = None;
+                this.redraw_at = None;
+                // let widgets look at the time in on_event too
+                this.queue.push(iced_native::Event::Mouse(mouse::Event::CursorMoved {
+                    position: this.cursor_position,
+                }));
+                this.render().await;
+            },
             () = leave_timeout => {
                 this.leave_timeout = None;
                 this.surface.on_pointer_leave().await;
@@ -695,6 +1033,8 @@ impl<T: DesktopSurface + IcedSurface> Runnable for IcedInstance<T> {
 
 impl<T: IcedSurface> Drop for IcedInstance<T> {
     fn drop(&mut self) {
+        dnd::unregister(&self.parent.wl_surface);
+        a11y::unregister_window(self.a11y_window);
         if let Some(tptr) = self.themed_ptr.take() {
             tptr.release();
         }
@@ -4,10 +4,18 @@
 pub mod event_loop;
 pub use event_loop::*;
 
+pub mod protocols;
+
+pub mod capture;
+
 #[macro_use]
 pub mod toplevels;
 pub use toplevels::*;
 
+#[macro_use]
+pub mod workspaces;
+pub use workspaces::*;
+
 pub mod run;
 pub use run::*;
 
@@ -17,14 +25,37 @@ pub use multimonitor::*;
 pub mod surfaces;
 pub use surfaces::*;
 
+pub mod gpu;
+
 pub mod iced;
 pub use iced::*;
 
+pub mod subscription;
+pub use subscription::*;
+
+pub mod a11y;
+pub use a11y::Role;
+
+pub mod dnd;
+pub use dnd::{DragData, DropData};
+
 pub mod widgets;
 pub use widgets::*;
 
+pub mod settings;
+pub use settings::{settings, ColorScheme, DesktopSettings};
+
 pub mod style;
 
+pub mod theme;
+pub use theme::{set_theme, theme, Theme};
+
+pub mod motion;
+pub use motion::reduced_motion;
+
+pub mod typography;
+pub use typography::{text_size, TextSize};
+
 pub mod handle;
 
 pub use iced_core;
@@ -37,16 +68,12 @@ pub use event_listener;
 macro_rules! wstk_main {
     ( $fun:ident ) => {
         fn main() -> Result<(), Box<dyn std::error::Error>> {
-            let main = glib::MainLoop::new(None, false);
-            let _main_guard = Box::leak(Box::new(glib::MainContext::default().acquire()?));
-            let (env, disp, queue) = make_env()?;
-            let env: &'static Environment<Env> = Box::leak(Box::new(env));
-            let disp: &'static Display = Box::leak(Box::new(disp));
-            let queue: &'static mut EventQueue = Box::leak(Box::new(queue));
-            glib_add_wayland(queue);
-            glib::MainContext::default().spawn_local($fun(env, disp));
-            main.run();
-            Ok(())
+            run_main($fun, OnDisconnect::Exit)
+        }
+    };
+    ( $fun:ident, $on_disconnect:expr ) => {
+        fn main() -> Result<(), Box<dyn std::error::Error>> {
+            run_main($fun, $on_disconnect)
         }
     };
 }
@@ -0,0 +1,21 @@
+//! Reduced motion: when the desktop turns animations off (or the theme file sets `[motion] reduced`),
+//! transitions and hover delays are skipped, so things change instantly instead of moving around.
+//! Anything animated or delayed should take its duration through `duration`.
+
+use std::time::Duration;
+
+/// How long the pointer can be outside of a surface before it counts as having left
+pub const POINTER_LEAVE_DELAY: Duration = Duration::from_millis(200);
+
+pub fn reduced_motion() -> bool {
+    crate::theme().motion.reduced
+}
+
+/// The duration of a transition or hover delay, zero with reduced motion
+pub fn duration(normal: Duration) -> Duration {
+    if reduced_motion() {
+        Duration::ZERO
+    } else {
+        normal
+    }
+}
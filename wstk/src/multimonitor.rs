@@ -18,7 +18,7 @@ where
 {
     pub async fn new(
         mk: Box<dyn 'a + Fn(wl_output::WlOutput, output::OutputInfo) -> LocalBoxFuture<'a, T>>,
-        env: &'a Environment<Env>,
+        env: &Environment<Env>,
     ) -> MultiMonitor<'a, T> {
         let (tx, rx) = mpsc::unbounded();
         let mut instances = Vec::new();
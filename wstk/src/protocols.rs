@@ -0,0 +1,38 @@
+//! Client bindings for protocols generated from protocols/*.xml by build.rs,
+//! like wayland-protocols does for the ones it has.
+
+macro_rules! client_protocol {
+    // the second list is for interfaces from the other protocols in this file
+    ($module:ident, $name:expr, [$($import:ident),*], [$(($prot:ident, $prot_import:ident)),*]) => {
+        pub mod $module {
+            #![allow(dead_code, non_camel_case_types, unused_unsafe, unused_variables)]
+            #![allow(non_upper_case_globals, non_snake_case, unused_imports)]
+            #![allow(missing_docs, clippy::all)]
+
+            pub(crate) use wayland_client::{AnonymousObject, Attached, Main, Proxy, ProxyMap};
+            pub(crate) use wayland_client::protocol::{$($import),*};
+            pub(crate) use wayland_client::sys;
+            pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
+            pub(crate) use wayland_commons::smallvec;
+            pub(crate) use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc};
+            pub(crate) use wayland_commons::{Interface, MessageGroup};
+            $(pub(crate) use crate::protocols::$prot::$prot_import;)*
+            include!(concat!(env!("OUT_DIR"), "/", $name, "_client_api.rs"));
+        }
+    };
+}
+
+client_protocol!(ext_foreign_toplevel_list, "ext-foreign-toplevel-list-v1", [], []);
+client_protocol!(
+    ext_image_capture_source,
+    "ext-image-capture-source-v1",
+    [wl_output],
+    [(ext_foreign_toplevel_list, ext_foreign_toplevel_handle_v1)]
+);
+client_protocol!(
+    ext_image_copy_capture,
+    "ext-image-copy-capture-v1",
+    [wl_buffer, wl_output, wl_pointer, wl_shm],
+    [(ext_image_capture_source, ext_image_capture_source_v1)]
+);
+client_protocol!(ext_workspace, "ext-workspace-v1", [wl_output], []);
@@ -0,0 +1,351 @@
+//! Desktop preferences (color scheme, accent color etc.) read from the settings portal,
+//! or directly from GSettings when there's no portal. The theme follows them.
+
+use futures::prelude::*;
+use gio::prelude::*;
+use iced_core::Color;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+const APPEARANCE: &str = "org.freedesktop.appearance";
+
+const PORTAL_NAMESPACES: &[&str] = &[
+    "org.gnome.desktop.interface",
+    "org.gnome.desktop.a11y.interface",
+    APPEARANCE,
+];
+
+/// GSettings schemas and keys to read when there's no portal
+const GSETTINGS_KEYS: &[(&str, &[&str])] = &[
+    (
+        "org.gnome.desktop.interface",
+        &[
+            "color-scheme",
+            "accent-color",
+            "font-name",
+            "monospace-font-name",
+            "text-scaling-factor",
+            "enable-animations",
+        ],
+    ),
+    ("org.gnome.desktop.a11y.interface", &["high-contrast"]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorScheme {
+    NoPreference,
+    Dark,
+    Light,
+}
+
+impl Default for ColorScheme {
+    fn default() -> Self {
+        ColorScheme::NoPreference
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesktopSettings {
+    pub color_scheme: ColorScheme,
+    pub accent_color: Option<Color>,
+    pub high_contrast: bool,
+    /// Font family names
+    pub interface_font: Option<String>,
+    pub monospace_font: Option<String>,
+    pub text_scaling_factor: f64,
+    pub enable_animations: bool,
+}
+
+impl Default for DesktopSettings {
+    fn default() -> Self {
+        DesktopSettings {
+            color_scheme: ColorScheme::default(),
+            accent_color: None,
+            high_contrast: false,
+            interface_font: None,
+            monospace_font: None,
+            text_scaling_factor: 1.0,
+            enable_animations: true,
+        }
+    }
+}
+
+impl DesktopSettings {
+    /// Applies a value from the portal or GSettings
+    fn apply(&mut self, namespace: &str, key: &str, value: &glib::Variant) {
+        match (namespace, key) {
+            ("org.freedesktop.appearance", "color-scheme") => {
+                self.color_scheme = match value.get::<u32>() {
+                    Some(1) => ColorScheme::Dark,
+                    Some(2) => ColorScheme::Light,
+                    _ => ColorScheme::NoPreference,
+                };
+            }
+            ("org.gnome.desktop.interface", "color-scheme") => {
+                self.color_scheme = match value.str() {
+                    Some("prefer-dark") => ColorScheme::Dark,
+                    Some("prefer-light") => ColorScheme::Light,
+                    _ => ColorScheme::NoPreference,
+                };
+            }
+            ("org.freedesktop.appearance", "accent-color") => self.accent_color = portal_accent(value),
+            ("org.gnome.desktop.interface", "accent-color") => {
+                self.accent_color = value.str().and_then(gnome_accent);
+            }
+            ("org.freedesktop.appearance", "contrast") => self.high_contrast = value.get::<u32>() == Some(1),
+            ("org.gnome.desktop.a11y.interface", "high-contrast") => {
+                self.high_contrast = value.get::<bool>().unwrap_or(false)
+            }
+            ("org.gnome.desktop.interface", "font-name") => self.interface_font = value.str().and_then(font_family),
+            ("org.gnome.desktop.interface", "monospace-font-name") => {
+                self.monospace_font = value.str().and_then(font_family)
+            }
+            ("org.gnome.desktop.interface", "text-scaling-factor") => {
+                self.text_scaling_factor = value.get::<f64>().filter(|f| *f > 0.0).unwrap_or(1.0)
+            }
+            ("org.gnome.desktop.interface", "enable-animations") => {
+                self.enable_animations = value.get::<bool>().unwrap_or(true)
+            }
+            ("org.freedesktop.appearance", "reduced-motion") => self.enable_animations = value.get::<u32>() != Some(1),
+            _ => (),
+        }
+    }
+}
+
+/// The portal's values by namespace and key
+type PortalValues = HashMap<String, HashMap<String, glib::Variant>>;
+
+/// Applies the portal's values in order, so that the cross-desktop keys win over the GNOME ones,
+/// but only when they state a preference: "no preference" falls through to the GNOME value.
+fn apply_portal(s: &mut DesktopSettings, all: &PortalValues) {
+    *s = DesktopSettings::default();
+    for ns in PORTAL_NAMESPACES {
+        for (key, value) in all.get(*ns).into_iter().flatten() {
+            if *ns == APPEARANCE && !is_preference(key, value) {
+                continue;
+            }
+            s.apply(ns, key, value);
+        }
+    }
+}
+
+/// Whether a cross-desktop value is an actual preference
+fn is_preference(key: &str, value: &glib::Variant) -> bool {
+    match key {
+        "color-scheme" => matches!(value.get::<u32>(), Some(1) | Some(2)),
+        "contrast" | "reduced-motion" => value.get::<u32>() == Some(1),
+        "accent-color" => portal_accent(value).is_some(),
+        _ => true,
+    }
+}
+
+/// Out of range values mean no preference
+fn portal_accent(value: &glib::Variant) -> Option<Color> {
+    value
+        .get::<(f64, f64, f64)>()
+        .filter(|(r, g, b)| [r, g, b].iter().all(|c| (0.0..=1.0).contains(*c)))
+        .map(|(r, g, b)| Color::from_rgb(r as f32, g as f32, b as f32))
+}
+
+/// The family from a Pango font description like "Cantarell Bold 11"
+fn font_family(desc: &str) -> Option<String> {
+    const STYLE_WORDS: &[&str] = &[
+        "Thin",
+        "Light",
+        "Regular",
+        "Medium",
+        "Semi-Bold",
+        "Bold",
+        "Heavy",
+        "Italic",
+        "Oblique",
+    ];
+    let mut words = desc.split_whitespace().collect::<Vec<_>>();
+    if words.last().map(|w| w.parse::<f64>().is_ok()).unwrap_or(false) {
+        words.pop();
+    }
+    while words.len() > 1 && words.last().map(|w| STYLE_WORDS.contains(w)).unwrap_or(false) {
+        words.pop();
+    }
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" "))
+    }
+}
+
+/// GNOME's named accent colors
+fn gnome_accent(name: &str) -> Option<Color> {
+    let (r, g, b) = match name {
+        "blue" => (0x35, 0x84, 0xe4),
+        "teal" => (0x21, 0x90, 0xa4),
+        "green" => (0x3a, 0x94, 0x4a),
+        "yellow" => (0xc8, 0x88, 0x00),
+        "orange" => (0xed, 0x5b, 0x00),
+        "red" => (0xe6, 0x2d, 0x42),
+        "pink" => (0xd5, 0x61, 0x99),
+        "purple" => (0x91, 0x41, 0xac),
+        "slate" => (0x6f, 0x83, 0x96),
+        _ => return None,
+    };
+    Some(Color::from_rgb8(r, g, b))
+}
+
+thread_local! {
+    static CURRENT: RefCell<DesktopSettings> = RefCell::new(DesktopSettings::default());
+    static CHANGED: event_listener::Event = event_listener::Event::new();
+    static GSETTINGS: RefCell<Vec<gio::Settings>> = RefCell::new(Vec::new());
+}
+
+pub fn settings() -> DesktopSettings {
+    CURRENT.with(|c| c.borrow().clone())
+}
+
+/// Resolves when any of the settings change
+pub fn settings_changed() -> impl FusedFuture<Output = ()> {
+    CHANGED.with(|n| n.listen()).fuse()
+}
+
+fn update(f: impl FnOnce(&mut DesktopSettings)) {
+    let changed = CURRENT.with(|c| {
+        let mut new = c.borrow().clone();
+        f(&mut new);
+        if *c.borrow() == new {
+            return false;
+        }
+        c.replace(new);
+        true
+    });
+    if changed {
+        CHANGED.with(|n| n.notify(usize::MAX));
+        crate::theme::reload();
+    }
+}
+
+/// Reads the settings and keeps watching them
+pub(crate) async fn watch() {
+    if let Err(e) = watch_portal().await {
+        eprintln!("Settings portal not available ({}), using GSettings", e);
+        watch_gsettings();
+    }
+}
+
+async fn watch_portal() -> Result<(), glib::Error> {
+    let bus = gio::bus_get_future(gio::BusType::Session).await?;
+    let reply = bus
+        .call_future(
+            Some("org.freedesktop.portal.Desktop"),
+            "/org/freedesktop/portal/desktop",
+            "org.freedesktop.portal.Settings",
+            "ReadAll",
+            Some(&(PORTAL_NAMESPACES.iter().map(|ns| ns.to_string()).collect::<Vec<_>>(),).to_variant()),
+            Some(glib::VariantTy::new("(a{sa{sv}})").unwrap()),
+            gio::DBusCallFlags::NONE,
+            -1,
+        )
+        .await?;
+    let all = Rc::new(RefCell::new(
+        reply.child_value(0).get::<PortalValues>().unwrap_or_default(),
+    ));
+    update(|s| apply_portal(s, &all.borrow()));
+    bus.signal_subscribe(
+        Some("org.freedesktop.portal.Desktop"),
+        Some("org.freedesktop.portal.Settings"),
+        Some("SettingChanged"),
+        Some("/org/freedesktop/portal/desktop"),
+        None,
+        gio::DBusSignalFlags::NONE,
+        move |_, _, _, _, _, params| {
+            let ns = params.child_value(0);
+            let key = params.child_value(1);
+            let value = params.child_value(2).as_variant();
+            if let (Some(ns), Some(key), Some(value)) = (ns.str(), key.str(), value) {
+                if !PORTAL_NAMESPACES.contains(&ns) {
+                    return;
+                }
+                // everything is applied again, a GNOME value might have been hidden by a cross-desktop one or not
+                all.borrow_mut()
+                    .entry(ns.to_owned())
+                    .or_default()
+                    .insert(key.to_owned(), value);
+                update(|s| apply_portal(s, &all.borrow()));
+            }
+        },
+    );
+    Ok(())
+}
+
+fn watch_gsettings() {
+    let source = match gio::SettingsSchemaSource::default() {
+        Some(s) => s,
+        None => return,
+    };
+    for (schema_id, keys) in GSETTINGS_KEYS {
+        // Settings::new aborts on a missing schema, so look it up first
+        let schema = match source.lookup(schema_id, true) {
+            Some(s) => s,
+            None => continue,
+        };
+        let gsettings = gio::Settings::new_full(&schema, None as Option<&gio::SettingsBackend>, None);
+        update(|s| {
+            for key in keys.iter().filter(|k| schema.has_key(k)) {
+                s.apply(schema_id, key, &gsettings.value(key));
+            }
+        });
+        gsettings.connect_changed(None, move |gsettings, key| {
+            update(|s| s.apply(schema_id, key, &gsettings.value(key)));
+        });
+        GSETTINGS.with(|g| g.borrow_mut().push(gsettings));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn portal(values: &[(&str, &str, glib::Variant)]) -> DesktopSettings {
+        let mut all = PortalValues::new();
+        for (ns, key, value) in values {
+            all.entry(ns.to_string())
+                .or_default()
+                .insert(key.to_string(), value.clone());
+        }
+        let mut s = DesktopSettings::default();
+        apply_portal(&mut s, &all);
+        s
+    }
+
+    #[test]
+    fn cross_desktop_preference_wins() {
+        let s = portal(&[
+            (
+                "org.gnome.desktop.interface",
+                "color-scheme",
+                "prefer-dark".to_variant(),
+            ),
+            (APPEARANCE, "color-scheme", 2u32.to_variant()),
+        ]);
+        assert_eq!(s.color_scheme, ColorScheme::Light);
+    }
+
+    #[test]
+    fn no_preference_falls_through() {
+        let s = portal(&[
+            (
+                "org.gnome.desktop.interface",
+                "color-scheme",
+                "prefer-dark".to_variant(),
+            ),
+            ("org.gnome.desktop.interface", "accent-color", "teal".to_variant()),
+            ("org.gnome.desktop.a11y.interface", "high-contrast", true.to_variant()),
+            (APPEARANCE, "color-scheme", 0u32.to_variant()),
+            (APPEARANCE, "accent-color", (-1.0f64, -1.0f64, -1.0f64).to_variant()),
+            (APPEARANCE, "contrast", 0u32.to_variant()),
+        ]);
+        assert_eq!(s.color_scheme, ColorScheme::Dark);
+        assert_eq!(s.accent_color, gnome_accent("teal"));
+        assert!(s.high_contrast);
+
+        let s = portal(&[(APPEARANCE, "color-scheme", 0u32.to_variant())]);
+        assert_eq!(s.color_scheme, ColorScheme::NoPreference);
+    }
+}
@@ -2,31 +2,27 @@ use crate::*;
 pub use iced_core::{Background, Color};
 use iced_native::widget::*;
 
-pub const DARK_COLOR: Color = Color::from_rgba(0.0784, 0.0784, 0.0784, 0.85);
-pub const RUNNING_DARK_COLOR: Color = Color::from_rgba(0.1584, 0.1584, 0.1784, 0.85);
-pub const BRIGHT_COLOR: Color = Color::from_rgba(0.874, 0.874, 0.874, 0.85);
-pub const VERY_BRIGHT_COLOR: Color = Color::from_rgba(0.89, 0.89, 0.89, 0.98);
-pub const SEL_COLOR: Color = Color::from_rgba(0.8, 0.8, 0.99, 0.69);
-
 pub struct DarkBar;
 
 impl container::StyleSheet for DarkBar {
     fn style(&self) -> container::Style {
         container::Style {
-            background: Some(Background::Color(Color::from_rgba8(0, 0, 0, 0.95))),
+            background: Some(Background::Color(theme().palette.overlay)),
             ..container::Style::default()
         }
     }
 }
 
+/// Background color given explicitly, usually one of the theme's backgrounds
 pub struct Dock(pub Color);
 
 impl container::StyleSheet for Dock {
     fn style(&self) -> container::Style {
+        let theme = theme();
         container::Style {
             background: Some(Background::Color(self.0)),
-            text_color: Some(Color::WHITE),
-            border_radius: 3.0,
+            text_color: Some(theme.palette.foreground),
+            border_radius: theme.metrics.radius,
             ..container::Style::default()
         }
     }
@@ -41,44 +37,157 @@ impl button::StyleSheet for Dock {
     }
 }
 
+pub struct Tooltip;
+
+impl container::StyleSheet for Tooltip {
+    fn style(&self) -> container::Style {
+        let theme = theme();
+        container::Style {
+            background: Some(Background::Color(theme.palette.background)),
+            border_width: theme.metrics.border_width,
+            border_color: theme.palette.border,
+            border_radius: theme.metrics.radius,
+            text_color: Some(theme.palette.text),
+        }
+    }
+}
+
+pub struct Menu;
+
+impl container::StyleSheet for Menu {
+    fn style(&self) -> container::Style {
+        let theme = theme();
+        container::Style {
+            background: Some(Background::Color(theme.palette.background_strong)),
+            border_width: theme.metrics.border_width,
+            border_color: theme.palette.border,
+            border_radius: theme.metrics.radius,
+            text_color: Some(theme.palette.text),
+        }
+    }
+}
+
+impl button::StyleSheet for Menu {
+    fn active(&self) -> button::Style {
+        let theme = theme();
+        button::Style {
+            background: None,
+            border_radius: theme.metrics.radius,
+            text_color: theme.palette.text,
+            ..button::Style::default()
+        }
+    }
+
+    fn hovered(&self) -> button::Style {
+        let theme = theme();
+        button::Style {
+            background: Some(Background::Color(theme.palette.selection)),
+            text_color: theme.palette.background,
+            ..self.active()
+        }
+    }
+}
+
 pub struct Toplevel;
 
 impl button::StyleSheet for Toplevel {
     fn active(&self) -> button::Style {
+        let theme = theme();
         button::Style {
-            background: Some(Background::Color(DARK_COLOR)),
-            border_radius: 3.0,
-            text_color: Color::WHITE,
+            background: Some(Background::Color(theme.palette.background)),
+            border_radius: theme.metrics.radius,
+            text_color: theme.palette.foreground,
             ..button::Style::default()
         }
     }
 
     fn hovered(&self) -> button::Style {
         button::Style {
-            background: Some(Background::Color(Color::from_rgba8(69, 69, 69, 0.85))),
+            background: Some(Background::Color(theme().palette.hover)),
             ..self.active()
         }
     }
 
     fn pressed(&self) -> button::Style {
+        let theme = theme();
         button::Style {
-            border_width: 1.0,
-            border_color: Color::WHITE,
+            border_width: theme.metrics.border_width,
+            border_color: theme.palette.foreground,
             ..self.hovered()
         }
     }
 }
 
+/// Window switcher items, the bool is whether it's selected
+pub struct Switcher(pub bool);
+
+impl button::StyleSheet for Switcher {
+    fn active(&self) -> button::Style {
+        let theme = theme();
+        button::Style {
+            background: if self.0 {
+                Some(Background::Color(theme.palette.selection))
+            } else {
+                None
+            },
+            border_radius: theme.metrics.radius,
+            text_color: if self.0 {
+                theme.palette.background
+            } else {
+                theme.palette.foreground
+            },
+            ..button::Style::default()
+        }
+    }
+
+    fn hovered(&self) -> button::Style {
+        if self.0 {
+            return self.active();
+        }
+        button::Style {
+            background: Some(Background::Color(theme().palette.hover)),
+            ..self.active()
+        }
+    }
+}
+
+/// Workspace switcher buttons in the dock
+pub struct Workspace {
+    pub active: bool,
+    pub urgent: bool,
+}
+
+impl button::StyleSheet for Workspace {
+    fn active(&self) -> button::Style {
+        let theme = theme();
+        button::Style {
+            border_width: if self.urgent { theme.metrics.border_width } else { 0.0 },
+            border_color: theme.palette.focus,
+            ..Switcher(self.active).active()
+        }
+    }
+
+    fn hovered(&self) -> button::Style {
+        let active = self.active();
+        button::Style {
+            border_width: active.border_width,
+            border_color: active.border_color,
+            ..Switcher(self.active).hovered()
+        }
+    }
+}
+
 pub struct Dialog;
 
 impl container::StyleSheet for Dialog {
     fn style(&self) -> container::Style {
+        let theme = theme();
         container::Style {
-            background: Some(Background::Color(DARK_COLOR)),
-            border_width: 1.0,
-            border_color: BRIGHT_COLOR,
-            border_radius: 3.0,
-            text_color: Some(VERY_BRIGHT_COLOR),
+            background: Some(Background::Color(theme.palette.background)),
+            border_width: theme.metrics.border_width,
+            border_color: theme.palette.text_dim,
+            border_radius: theme.metrics.radius,
+            text_color: Some(theme.palette.text),
             ..container::Style::default()
         }
     }
@@ -86,40 +195,43 @@ impl container::StyleSheet for Dialog {
 
 impl text_input::StyleSheet for Dialog {
     fn active(&self) -> text_input::Style {
+        let theme = theme();
         text_input::Style {
-            background: Background::Color(Color::from_rgba8(100, 100, 100, 0.45)),
-            border_radius: 3.0,
-            border_width: 1.0,
-            border_color: Color::from_rgba8(255, 255, 255, 0.45),
+            background: Background::Color(theme.palette.input),
+            border_radius: theme.metrics.radius,
+            border_width: theme.metrics.border_width,
+            border_color: theme.palette.input_border,
         }
     }
 
     fn focused(&self) -> text_input::Style {
+        let theme = theme();
         text_input::Style {
-            background: Background::Color(VERY_BRIGHT_COLOR),
-            border_color: BRIGHT_COLOR,
+            background: Background::Color(theme.palette.input_focused),
+            border_color: theme.palette.text_dim,
             ..self.active()
         }
     }
 
     fn hovered(&self) -> text_input::Style {
+        let theme = theme();
         text_input::Style {
-            background: Background::Color(Color::from_rgba8(200, 200, 200, 0.55)),
-            border_color: Color::from_rgba8(255, 255, 255, 0.55),
+            background: Background::Color(theme.palette.input_hover),
+            border_color: theme.palette.input_hover_border,
             ..self.active()
         }
     }
 
     fn placeholder_color(&self) -> Color {
-        Color::from_rgb(0.4, 0.4, 0.4)
+        theme().palette.placeholder
     }
 
     fn value_color(&self) -> Color {
-        DARK_COLOR
+        theme().palette.input_text
     }
 
     fn selection_color(&self) -> Color {
-        SEL_COLOR
+        theme().palette.selection
     }
 }
 
@@ -132,38 +244,43 @@ pub struct Action(pub ActionType);
 
 impl button::StyleSheet for Action {
     fn active(&self) -> button::Style {
+        let theme = theme();
+        let p = &theme.palette;
         button::Style {
             background: Some(Background::Color(match &self.0 {
-                ActionType::Bad => Color::from_rgba8(155, 55, 55, 0.85),
-                ActionType::Good => Color::from_rgba8(55, 155, 55, 0.85),
+                ActionType::Bad => p.bad,
+                ActionType::Good => p.good,
             })),
-            border_radius: 69.0,
-            border_width: 1.0,
+            border_radius: theme.metrics.pill_radius,
+            border_width: theme.metrics.border_width,
             border_color: match &self.0 {
-                ActionType::Bad => Color::from_rgba8(205, 105, 105, 0.85),
-                ActionType::Good => Color::from_rgba8(105, 205, 105, 0.85),
+                ActionType::Bad => p.bad_border,
+                ActionType::Good => p.good_border,
             },
-            text_color: BRIGHT_COLOR,
+            text_color: p.text_dim,
             ..button::Style::default()
         }
     }
 
     fn hovered(&self) -> button::Style {
+        let theme = theme();
+        let p = &theme.palette;
         button::Style {
             background: Some(Background::Color(match &self.0 {
-                ActionType::Bad => Color::from_rgba8(255, 155, 155, 0.85),
-                ActionType::Good => Color::from_rgba8(155, 255, 155, 0.85),
+                ActionType::Bad => p.bad_hover,
+                ActionType::Good => p.good_hover,
             })),
             border_width: 0.0,
-            text_color: DARK_COLOR,
+            text_color: p.background,
             ..self.active()
         }
     }
 
     fn pressed(&self) -> button::Style {
+        let theme = theme();
         button::Style {
-            border_color: BRIGHT_COLOR,
-            border_width: 1.0,
+            border_color: theme.palette.text_dim,
+            border_width: theme.metrics.border_width,
             ..self.hovered()
         }
     }
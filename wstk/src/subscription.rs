@@ -0,0 +1,190 @@
+//! Declarative timers and streams for surfaces, managed by the IcedInstance
+
+use futures::{
+    future,
+    prelude::*,
+    stream::{self, LocalBoxStream},
+    task,
+};
+use std::{collections::HashMap, time::Duration};
+
+/// A source of messages, identified by a string id.
+/// Subscriptions are collected from the surface after every update:
+/// new ids get started, ids that disappear get dropped, and existing ones keep running untouched.
+pub struct Subscription<Message> {
+    id: String,
+    make: Box<dyn FnOnce() -> LocalBoxStream<'static, Message>>,
+}
+
+impl<Message: 'static> Subscription<Message> {
+    /// Produces the message every `period`.
+    pub fn interval(id: impl Into<String>, period: Duration, msg: Message) -> Self
+    where
+        Message: Clone,
+    {
+        Self::stream(id, move || glib::interval_stream(period).map(move |()| msg.clone()))
+    }
+
+    /// Produces the message once, after `delay`.
+    /// To restart the delay, stop returning the subscription for one update, or change its id.
+    pub fn after(id: impl Into<String>, delay: Duration, msg: Message) -> Self {
+        Self::stream(id, move || glib::timeout_future(delay).map(move |()| msg).into_stream())
+    }
+
+    /// Maps an arbitrary stream into messages. `make` is only called when the subscription is started.
+    pub fn stream<S, F>(id: impl Into<String>, make: F) -> Self
+    where
+        F: FnOnce() -> S + 'static,
+        S: Stream<Item = Message> + 'static,
+    {
+        Subscription {
+            id: id.into(),
+            make: Box::new(move || make().boxed_local()),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Converts the messages, e.g. to wrap a component's message into the surface's one.
+    pub fn map<B: 'static>(self, f: impl Fn(Message) -> B + 'static) -> Subscription<B> {
+        let make = self.make;
+        Subscription {
+            id: self.id,
+            make: Box::new(move || make().map(f).boxed_local()),
+        }
+    }
+
+    /// Prepends a prefix to the id, for keeping ids of multiple components apart.
+    pub fn prefix_id(mut self, prefix: &str) -> Self {
+        self.id.insert_str(0, prefix);
+        self
+    }
+}
+
+/// The running subscriptions of a surface.
+pub struct Subscriptions<Message> {
+    // finished streams stay here (terminated) so that they don't get restarted
+    running: HashMap<String, stream::Fuse<LocalBoxStream<'static, Message>>>,
+}
+
+impl<Message: 'static> Subscriptions<Message> {
+    pub fn new() -> Self {
+        Subscriptions {
+            running: HashMap::new(),
+        }
+    }
+
+    /// Starts new subscriptions and drops the ones not present in `subs` anymore.
+    pub fn update(&mut self, subs: Vec<Subscription<Message>>) {
+        let mut old = std::mem::take(&mut self.running);
+        for sub in subs {
+            let stream = match old.remove(&sub.id) {
+                Some(s) => s,
+                None => (sub.make)().fuse(),
+            };
+            self.running.insert(sub.id, stream);
+        }
+    }
+
+    /// Waits for the next message from any of the subscriptions.
+    pub fn next(&mut self) -> impl future::FusedFuture<Output = Message> + '_ {
+        future::poll_fn(move |cx| {
+            for s in self.running.values_mut() {
+                if s.is_done() {
+                    continue;
+                }
+                if let task::Poll::Ready(Some(msg)) = s.poll_next_unpin(cx) {
+                    return task::Poll::Ready(msg);
+                }
+            }
+            task::Poll::Pending
+        })
+        .fuse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, rc::Rc};
+
+    /// The next message, None if there's none within the timeout
+    fn next_within<M: 'static>(ctx: &glib::MainContext, subs: &mut Subscriptions<M>, timeout: Duration) -> Option<M> {
+        ctx.block_on(async {
+            futures::select! {
+                msg = subs.next() => Some(msg),
+                () = glib::timeout_future(timeout).fuse() => None,
+            }
+        })
+    }
+
+    const WAIT: Duration = Duration::from_secs(5);
+    const NOTHING: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn interval_fires_repeatedly() {
+        let ctx = glib::MainContext::new();
+        let mut subs = Subscriptions::new();
+        subs.update(vec![Subscription::interval("tick", Duration::from_millis(1), "tick")]);
+        for _ in 0..3 {
+            assert_eq!(next_within(&ctx, &mut subs, WAIT), Some("tick"));
+        }
+    }
+
+    #[test]
+    fn after_fires_once() {
+        let ctx = glib::MainContext::new();
+        let mut subs = Subscriptions::new();
+        subs.update(vec![Subscription::after("later", Duration::from_millis(1), "later")]);
+        assert_eq!(next_within(&ctx, &mut subs, WAIT), Some("later"));
+        assert_eq!(next_within(&ctx, &mut subs, NOTHING), None);
+
+        // still there, so not restarted
+        subs.update(vec![Subscription::after("later", Duration::from_millis(1), "later")]);
+        assert_eq!(next_within(&ctx, &mut subs, NOTHING), None);
+    }
+
+    #[test]
+    fn stream_maps_to_messages() {
+        let ctx = glib::MainContext::new();
+        let mut subs = Subscriptions::new();
+        let sub = Subscription::stream("numbers", || stream::iter(vec![1, 2]))
+            .map(|n| n * 10)
+            .prefix_id("dock:");
+        assert_eq!(sub.id(), "dock:numbers");
+        subs.update(vec![sub]);
+        assert_eq!(next_within(&ctx, &mut subs, WAIT), Some(10));
+        assert_eq!(next_within(&ctx, &mut subs, WAIT), Some(20));
+        assert_eq!(next_within(&ctx, &mut subs, NOTHING), None);
+    }
+
+    #[test]
+    fn same_id_kept_removed_dropped() {
+        let ctx = glib::MainContext::new();
+        fn counting(made: &Rc<Cell<i32>>) -> Subscription<i32> {
+            let made = made.clone();
+            Subscription::stream("counting", move || {
+                made.set(made.get() + 1);
+                stream::iter(vec![1, 2, 3])
+            })
+        }
+        let made = Rc::new(Cell::new(0));
+        let mut subs = Subscriptions::new();
+        subs.update(vec![counting(&made)]);
+        assert_eq!(next_within(&ctx, &mut subs, WAIT), Some(1));
+
+        // kept running where it was, not made again
+        subs.update(vec![counting(&made), Subscription::stream("other", stream::pending)]);
+        assert_eq!(next_within(&ctx, &mut subs, WAIT), Some(2));
+        assert_eq!(made.get(), 1);
+
+        // dropped, then started from scratch when it's back
+        subs.update(vec![]);
+        assert_eq!(next_within(&ctx, &mut subs, NOTHING), None);
+        subs.update(vec![counting(&made)]);
+        assert_eq!(made.get(), 2);
+        assert_eq!(next_within(&ctx, &mut subs, WAIT), Some(1));
+    }
+}
@@ -24,28 +24,60 @@ use std::rc::Rc;
 use futures::channel::mpsc;
 pub use futures::prelude::*;
 
-use crate::{event_loop::AsyncMain, handle::*, toplevels::*};
+use crate::{
+    capture::{copy_manager, screencopy_manager, toplevel_source_manager},
+    dnd,
+    event_loop::{is_connection_lost, AsyncMain},
+    handle::*,
+    toplevels::*,
+    workspaces::*,
+};
 
 default_environment!(Env,
     fields = [
         layer_shell: SimpleGlobal<layer_shell::ZwlrLayerShellV1>,
         toplevel_service: ToplevelServiceRc,
+        ext_toplevel_list: ExtToplevelListRc,
+        toplevel_capture_sources: SimpleGlobal<toplevel_source_manager::ExtForeignToplevelImageCaptureSourceManagerV1>,
+        image_copy_capture: SimpleGlobal<copy_manager::ExtImageCopyCaptureManagerV1>,
+        screencopy: SimpleGlobal<screencopy_manager::ZwlrScreencopyManagerV1>,
+        workspace_service: WorkspaceServiceRc,
     ],
     singles = [
         layer_shell::ZwlrLayerShellV1 => layer_shell,
         toplevel_manager::ZwlrForeignToplevelManagerV1 => toplevel_service,
+        ext_toplevel_list::ExtForeignToplevelListV1 => ext_toplevel_list,
+        toplevel_source_manager::ExtForeignToplevelImageCaptureSourceManagerV1 => toplevel_capture_sources,
+        copy_manager::ExtImageCopyCaptureManagerV1 => image_copy_capture,
+        screencopy_manager::ZwlrScreencopyManagerV1 => screencopy,
+        workspace_manager::ExtWorkspaceManagerV1 => workspace_service,
     ],
 );
 toplevel_handler!(Env, toplevel_service);
+workspace_handler!(Env, workspace_service);
 
 pub fn make_env() -> Result<(Environment<Env>, Display, EventQueue), ConnectError> {
-    new_default_environment!(
+    // both protocols feed the same service
+    let toplevels = Rc::new(ToplevelService::new());
+    let (mut env, display, queue) = new_default_environment!(
         Env,
         fields = [
             layer_shell: SimpleGlobal::new(),
-            toplevel_service: ToplevelServiceRc(Rc::new(ToplevelService::new())),
+            toplevel_service: ToplevelServiceRc(toplevels.clone()),
+            ext_toplevel_list: ExtToplevelListRc(toplevels),
+            toplevel_capture_sources: SimpleGlobal::new(),
+            image_copy_capture: SimpleGlobal::new(),
+            screencopy: SimpleGlobal::new(),
+            workspace_service: WorkspaceServiceRc(Rc::new(WorkspaceService::new())),
         ]
-    )
+    )?;
+    if env
+        .set_data_device_callback(|_seat, event, _| dnd::on_data_device_event(event))
+        .is_err()
+    {
+        eprintln!("No data device manager, drag and drop won't work");
+    }
+    Ok((env, display, queue))
 }
 
 static mut SCALE_CHANNELS: Vec<(wl_surface::WlSurface, mpsc::UnboundedSender<i32>)> = Vec::new();
@@ -115,7 +147,12 @@ impl DesktopInstance {
     }
 
     pub fn flush(&self) {
-        self.display.flush().unwrap();
+        // a broken connection is handled (and reported) by the event loop
+        if let Err(e) = self.display.flush() {
+            if e.kind() != std::io::ErrorKind::WouldBlock && !is_connection_lost() {
+                eprintln!("Error flushing the wayland socket: {:?}", e);
+            }
+        }
     }
 
     pub fn create_region(&self) -> Main<wl_region::WlRegion> {
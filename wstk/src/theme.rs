@@ -0,0 +1,491 @@
+//! Colors and metrics used by the stylesheets in `style`.
+//! The theme is loaded from $XDG_CONFIG_HOME/waysmoke/theme.ini (a GLib key file, missing keys
+//! fall back to the defaults) and reloaded when that file or the desktop settings change,
+//! running surfaces rerender with it. The light or dark palette is picked by the color scheme,
+//! colors-light and colors-dark override colors for each. The accent color is applied before
+//! the file's colors, high contrast adjustments after them. Fonts and text scaling also come from
+//! the desktop settings unless the file sets them, and so does reduced motion.
+//!
+//! ```ini
+//! [colors]
+//! selection=#ccccfcb0
+//! [colors-dark]
+//! background=#141414d9
+//! [metrics]
+//! radius=6
+//! [fonts]
+//! interface=Inter
+//! monospace=Source Code Pro
+//! scale=1.25
+//! [motion]
+//! reduced=true
+//! ```
+
+use crate::{
+    settings::{settings, ColorScheme, DesktopSettings},
+    typography::TextSize,
+};
+use futures::prelude::*;
+use gio::prelude::*;
+use iced_core::Color;
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    /// Dock, popovers, tooltips, dialogs
+    pub background: Color,
+    /// Menus and other things that need to stand out from what's under them
+    pub background_strong: Color,
+    /// Dock items of running apps
+    pub background_raised: Color,
+    /// Screen-covering backdrops (the bar, dialog overlays)
+    pub overlay: Color,
+    /// Text and icons on the background
+    pub foreground: Color,
+    /// Text in tooltips, menus, dialogs
+    pub text: Color,
+    /// Secondary text and borders
+    pub text_dim: Color,
+    pub border: Color,
+    pub hover: Color,
+    pub selection: Color,
+    pub focus: Color,
+    pub input: Color,
+    pub input_hover: Color,
+    pub input_focused: Color,
+    pub input_border: Color,
+    pub input_hover_border: Color,
+    pub input_text: Color,
+    pub placeholder: Color,
+    pub good: Color,
+    pub good_border: Color,
+    pub good_hover: Color,
+    pub bad: Color,
+    pub bad_border: Color,
+    pub bad_hover: Color,
+}
+
+impl Palette {
+    pub fn dark() -> Palette {
+        Palette {
+            background: Color::from_rgba(0.0784, 0.0784, 0.0784, 0.85),
+            background_strong: Color::from_rgba(0.0784, 0.0784, 0.0784, 0.97),
+            background_raised: Color::from_rgba(0.1584, 0.1584, 0.1784, 0.85),
+            overlay: Color::from_rgba8(0, 0, 0, 0.95),
+            foreground: Color::WHITE,
+            text: Color::from_rgba(0.89, 0.89, 0.89, 0.98),
+            text_dim: Color::from_rgba(0.874, 0.874, 0.874, 0.85),
+            border: Color::from_rgba8(255, 255, 255, 0.25),
+            hover: Color::from_rgba8(69, 69, 69, 0.85),
+            selection: Color::from_rgba(0.8, 0.8, 0.99, 0.69),
+            focus: Color::from_rgba(0.53, 0.63, 1.0, 0.9),
+            input: Color::from_rgba8(100, 100, 100, 0.45),
+            input_hover: Color::from_rgba8(200, 200, 200, 0.55),
+            input_focused: Color::from_rgba(0.89, 0.89, 0.89, 0.98),
+            input_border: Color::from_rgba8(255, 255, 255, 0.45),
+            input_hover_border: Color::from_rgba8(255, 255, 255, 0.55),
+            input_text: Color::from_rgba(0.0784, 0.0784, 0.0784, 0.85),
+            placeholder: Color::from_rgb(0.4, 0.4, 0.4),
+            good: Color::from_rgba8(55, 155, 55, 0.85),
+            good_border: Color::from_rgba8(105, 205, 105, 0.85),
+            good_hover: Color::from_rgba8(155, 255, 155, 0.85),
+            bad: Color::from_rgba8(155, 55, 55, 0.85),
+            bad_border: Color::from_rgba8(205, 105, 105, 0.85),
+            bad_hover: Color::from_rgba8(255, 155, 155, 0.85),
+        }
+    }
+
+    pub fn light() -> Palette {
+        Palette {
+            background: Color::from_rgba(0.94, 0.94, 0.94, 0.88),
+            background_strong: Color::from_rgba(0.97, 0.97, 0.97, 0.98),
+            background_raised: Color::from_rgba(0.84, 0.84, 0.88, 0.88),
+            overlay: Color::from_rgba8(235, 235, 235, 0.95),
+            foreground: Color::from_rgb(0.1, 0.1, 0.1),
+            text: Color::from_rgba(0.12, 0.12, 0.12, 0.98),
+            text_dim: Color::from_rgba(0.25, 0.25, 0.25, 0.85),
+            border: Color::from_rgba8(0, 0, 0, 0.2),
+            hover: Color::from_rgba8(205, 205, 210, 0.85),
+            selection: Color::from_rgba(0.35, 0.45, 0.9, 0.6),
+            focus: Color::from_rgba(0.2, 0.35, 0.9, 0.9),
+            input: Color::from_rgba8(255, 255, 255, 0.7),
+            input_hover: Color::from_rgba8(255, 255, 255, 0.9),
+            input_focused: Color::WHITE,
+            input_border: Color::from_rgba8(0, 0, 0, 0.3),
+            input_hover_border: Color::from_rgba8(0, 0, 0, 0.45),
+            input_text: Color::from_rgb(0.1, 0.1, 0.1),
+            placeholder: Color::from_rgb(0.55, 0.55, 0.55),
+            good: Color::from_rgba8(80, 170, 80, 0.9),
+            good_border: Color::from_rgba8(40, 120, 40, 0.9),
+            good_hover: Color::from_rgba8(40, 130, 40, 0.9),
+            bad: Color::from_rgba8(190, 70, 70, 0.9),
+            bad_border: Color::from_rgba8(140, 40, 40, 0.9),
+            bad_hover: Color::from_rgba8(170, 40, 40, 0.9),
+        }
+    }
+
+    /// Dark unless light is preferred
+    pub fn for_scheme(scheme: ColorScheme) -> Palette {
+        match scheme {
+            ColorScheme::Light => Palette::light(),
+            ColorScheme::Dark | ColorScheme::NoPreference => Palette::dark(),
+        }
+    }
+
+    /// Derives the selection, focus, hover and "good" action colors from the accent color
+    pub fn apply_accent(&mut self, accent: Color, light: bool) {
+        let shade = if light { Color::BLACK } else { Color::WHITE };
+        self.selection = with_alpha(accent, self.selection.a);
+        self.focus = with_alpha(mix(accent, shade, 0.2), self.focus.a);
+        self.hover = with_alpha(mix(self.background, accent, 0.35), self.hover.a);
+        self.good = with_alpha(accent, self.good.a);
+        self.good_border = with_alpha(mix(accent, shade, 0.35), self.good_border.a);
+        self.good_hover = with_alpha(mix(accent, shade, 0.5), self.good_hover.a);
+    }
+
+    fn load(&mut self, kf: &glib::KeyFile, group: &str) {
+        for (key, color) in [
+            ("background", &mut self.background),
+            ("background-strong", &mut self.background_strong),
+            ("background-raised", &mut self.background_raised),
+            ("overlay", &mut self.overlay),
+            ("foreground", &mut self.foreground),
+            ("text", &mut self.text),
+            ("text-dim", &mut self.text_dim),
+            ("border", &mut self.border),
+            ("hover", &mut self.hover),
+            ("selection", &mut self.selection),
+            ("focus", &mut self.focus),
+            ("input", &mut self.input),
+            ("input-hover", &mut self.input_hover),
+            ("input-focused", &mut self.input_focused),
+            ("input-border", &mut self.input_border),
+            ("input-hover-border", &mut self.input_hover_border),
+            ("input-text", &mut self.input_text),
+            ("placeholder", &mut self.placeholder),
+            ("good", &mut self.good),
+            ("good-border", &mut self.good_border),
+            ("good-hover", &mut self.good_hover),
+            ("bad", &mut self.bad),
+            ("bad-border", &mut self.bad_border),
+            ("bad-hover", &mut self.bad_hover),
+        ] {
+            if let Ok(val) = kf.string(group, key) {
+                match parse_color(&val) {
+                    Some(c) => *color = c,
+                    None => eprintln!("Theme: bad color for {}: {}", key, val),
+                }
+            }
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::dark()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metrics {
+    /// Corners of containers and buttons
+    pub radius: f32,
+    /// Corners of the round action buttons
+    pub pill_radius: f32,
+    pub border_width: f32,
+    /// Width of the keyboard focus ring
+    pub focus_width: f32,
+    /// The gap between elements, paddings and larger gaps are multiples of it
+    pub spacing: u16,
+}
+
+impl Metrics {
+    fn load(&mut self, kf: &glib::KeyFile) {
+        for (key, val) in [
+            ("radius", &mut self.radius),
+            ("pill-radius", &mut self.pill_radius),
+            ("border-width", &mut self.border_width),
+            ("focus-width", &mut self.focus_width),
+        ] {
+            if let Ok(v) = kf.double("metrics", key) {
+                *val = v as f32;
+            }
+        }
+        if let Ok(v) = kf.integer("metrics", "spacing") {
+            self.spacing = v.try_into().unwrap_or(self.spacing);
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            radius: 3.0,
+            pill_radius: 69.0,
+            border_width: 1.0,
+            focus_width: 2.0,
+            spacing: 4,
+        }
+    }
+}
+
+/// Font family names, None means the renderer's default font
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Fonts {
+    pub interface: Option<String>,
+    pub monospace: Option<String>,
+}
+
+impl Fonts {
+    fn load(&mut self, kf: &glib::KeyFile) {
+        if let Ok(f) = kf.string("fonts", "interface") {
+            self.interface = Some(f.to_string());
+        }
+        if let Ok(f) = kf.string("fonts", "monospace") {
+            self.monospace = Some(f.to_string());
+        }
+    }
+}
+
+/// See the `motion` module
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Motion {
+    /// No transitions or hover delays
+    pub reduced: bool,
+}
+
+impl Motion {
+    fn load(&mut self, kf: &glib::KeyFile) {
+        if let Ok(v) = kf.boolean("motion", "reduced") {
+            self.reduced = v;
+        }
+    }
+}
+
+/// Text sizes in pixels before scaling, see the `typography` module
+#[derive(Debug, Clone, PartialEq)]
+pub struct Typography {
+    pub small: u16,
+    pub body: u16,
+    pub large: u16,
+    pub title: u16,
+    pub scale: f32,
+}
+
+impl Typography {
+    /// The scaled size in pixels
+    pub fn size(&self, size: TextSize) -> u16 {
+        let px = match size {
+            TextSize::Small => self.small,
+            TextSize::Body => self.body,
+            TextSize::Large => self.large,
+            TextSize::Title => self.title,
+        };
+        (px as f32 * self.scale).round() as u16
+    }
+
+    fn load(&mut self, kf: &glib::KeyFile) {
+        if let Ok(v) = kf.double("fonts", "scale") {
+            self.scale = v as f32;
+        }
+    }
+}
+
+impl Default for Typography {
+    fn default() -> Self {
+        Typography {
+            small: 14,
+            body: 16,
+            large: 18,
+            title: 20,
+            scale: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Theme {
+    pub palette: Palette,
+    pub metrics: Metrics,
+    pub fonts: Fonts,
+    pub typography: Typography,
+    pub motion: Motion,
+}
+
+impl Theme {
+    /// The defaults for the desktop settings (without the high contrast adjustments)
+    pub fn for_settings(settings: &DesktopSettings) -> Theme {
+        let mut theme = Theme {
+            palette: Palette::for_scheme(settings.color_scheme),
+            fonts: Fonts {
+                interface: settings.interface_font.clone(),
+                monospace: settings.monospace_font.clone(),
+            },
+            typography: Typography {
+                scale: settings.text_scaling_factor as f32,
+                ..Typography::default()
+            },
+            motion: Motion {
+                reduced: !settings.enable_animations,
+            },
+            ..Theme::default()
+        };
+        if let Some(accent) = settings.accent_color {
+            theme
+                .palette
+                .apply_accent(accent, settings.color_scheme == ColorScheme::Light);
+        }
+        theme
+    }
+
+    /// The defaults with whatever the key file overrides
+    pub fn from_key_file(kf: &glib::KeyFile, settings: &DesktopSettings) -> Theme {
+        let light = settings.color_scheme == ColorScheme::Light;
+        let mut theme = Theme::for_settings(settings);
+        theme.palette.load(kf, "colors");
+        theme
+            .palette
+            .load(kf, if light { "colors-light" } else { "colors-dark" });
+        theme.metrics.load(kf);
+        theme.fonts.load(kf);
+        theme.typography.load(kf);
+        theme.motion.load(kf);
+        if settings.high_contrast {
+            theme.apply_high_contrast(light);
+        }
+        theme
+    }
+
+    /// Solid backgrounds, full contrast text and borders, thicker borders and focus rings
+    pub fn apply_high_contrast(&mut self, light: bool) {
+        let p = &mut self.palette;
+        let fg = if light { Color::BLACK } else { Color::WHITE };
+        for c in [
+            &mut p.background,
+            &mut p.background_strong,
+            &mut p.background_raised,
+            &mut p.overlay,
+            &mut p.hover,
+            &mut p.selection,
+            &mut p.focus,
+            &mut p.input,
+            &mut p.input_hover,
+            &mut p.input_focused,
+            &mut p.good,
+            &mut p.good_hover,
+            &mut p.bad,
+            &mut p.bad_hover,
+        ] {
+            c.a = 1.0;
+        }
+        for c in [
+            &mut p.foreground,
+            &mut p.text,
+            &mut p.text_dim,
+            &mut p.border,
+            &mut p.input_border,
+            &mut p.input_hover_border,
+            &mut p.good_border,
+            &mut p.bad_border,
+        ] {
+            *c = fg;
+        }
+        self.metrics.border_width = self.metrics.border_width.max(2.0);
+        self.metrics.focus_width = self.metrics.focus_width.max(3.0);
+    }
+
+    /// Loads the user's theme file for the current desktop settings, or the defaults if there isn't one
+    pub fn load() -> Theme {
+        let kf = glib::KeyFile::new();
+        if let Err(e) = kf.load_from_file(theme_path(), glib::KeyFileFlags::NONE) {
+            if !e.matches(glib::FileError::Noent) {
+                eprintln!("Could not load the theme: {}", e);
+            }
+        }
+        Theme::from_key_file(&kf, &settings())
+    }
+}
+
+fn mix(a: Color, b: Color, t: f32) -> Color {
+    Color {
+        r: a.r + (b.r - a.r) * t,
+        g: a.g + (b.g - a.g) * t,
+        b: a.b + (b.b - a.b) * t,
+        a: a.a,
+    }
+}
+
+fn with_alpha(c: Color, a: f32) -> Color {
+    Color { a, ..c }
+}
+
+/// Parses #rrggbb and #rrggbbaa
+pub fn parse_color(s: &str) -> Option<Color> {
+    let hex = s.trim().strip_prefix('#')?;
+    if !hex.is_ascii() || (hex.len() != 6 && hex.len() != 8) {
+        return None;
+    }
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let a = if hex.len() == 8 { byte(6)? as f32 / 255.0 } else { 1.0 };
+    Some(Color::from_rgba8(byte(0)?, byte(2)?, byte(4)?, a))
+}
+
+pub fn theme_path() -> PathBuf {
+    glib::user_config_dir().join("waysmoke").join("theme.ini")
+}
+
+thread_local! {
+    static CURRENT: RefCell<Rc<Theme>> = RefCell::new(Rc::new(Theme::default()));
+    static CHANGED: event_listener::Event = event_listener::Event::new();
+    static MONITOR: RefCell<Option<gio::FileMonitor>> = RefCell::new(None);
+}
+
+/// The theme the stylesheets should use right now
+pub fn theme() -> Rc<Theme> {
+    CURRENT.with(|c| c.borrow().clone())
+}
+
+/// Replaces the theme, making running surfaces rerender if it's different
+pub fn set_theme(theme: Theme) {
+    let changed = CURRENT.with(|c| {
+        if **c.borrow() == theme {
+            return false;
+        }
+        c.replace(Rc::new(theme));
+        true
+    });
+    if changed {
+        CHANGED.with(|n| n.notify(usize::MAX));
+    }
+}
+
+/// Resolves when the theme changes
+pub fn theme_changed() -> impl FusedFuture<Output = ()> {
+    CHANGED.with(|n| n.listen()).fuse()
+}
+
+pub(crate) fn reload() {
+    set_theme(Theme::load());
+}
+
+/// Loads the user's theme file and keeps reloading it when it changes
+pub(crate) fn load_and_watch() {
+    reload();
+    let file = gio::File::for_path(theme_path());
+    match file.monitor_file(gio::FileMonitorFlags::NONE, None as Option<&gio::Cancellable>) {
+        Ok(monitor) => {
+            monitor.connect_changed(|_, _, _, event| {
+                if matches!(
+                    event,
+                    gio::FileMonitorEvent::ChangesDoneHint
+                        | gio::FileMonitorEvent::Created
+                        | gio::FileMonitorEvent::Deleted
+                ) {
+                    reload();
+                }
+            });
+            MONITOR.with(|m| m.replace(Some(monitor)));
+        }
+        Err(e) => eprintln!("Could not watch the theme file: {}", e),
+    }
+}
@@ -3,125 +3,509 @@ use futures::prelude::*;
 use smithay_client_toolkit::{
     environment::GlobalHandler,
     reexports::client::{
-        protocol::{wl_output, wl_registry},
-        Attached, DispatchData, Proxy,
+        protocol::{wl_output, wl_registry, wl_seat, wl_surface},
+        Attached, DispatchData,
     },
 };
 
+pub use crate::protocols::ext_foreign_toplevel_list::{
+    ext_foreign_toplevel_handle_v1 as ext_toplevel_handle, ext_foreign_toplevel_list_v1 as ext_toplevel_list,
+};
 pub use smithay_client_toolkit::reexports::protocols::wlr::unstable::foreign_toplevel::v1::client::{
     zwlr_foreign_toplevel_handle_v1 as toplevel_handle, zwlr_foreign_toplevel_manager_v1 as toplevel_manager,
 };
 
 use std::{
-    cell::{Ref, RefCell},
+    cell::{Cell, Ref, RefCell},
     collections::HashMap,
-    hash::{Hash, Hasher},
     rc::Rc,
+    time::Instant,
 };
 
-#[derive(PartialEq, Eq, Clone)]
-pub struct ToplevelKey(toplevel_handle::ZwlrForeignToplevelHandleV1);
+/// A toplevel from whichever protocol the compositor supports:
+/// wlr-foreign-toplevel-management, or ext-foreign-toplevel-list which can only list them
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ToplevelHandle {
+    Wlr(toplevel_handle::ZwlrForeignToplevelHandleV1),
+    Ext(ext_toplevel_handle::ExtForeignToplevelHandleV1),
+    /// Records the requests instead of sending them
+    #[cfg(test)]
+    Fake(Rc<tests::FakeHandle>),
+}
+
+impl ToplevelHandle {
+    /// The handle for window control, None when the backend can't do it
+    pub fn wlr(&self) -> Option<&toplevel_handle::ZwlrForeignToplevelHandleV1> {
+        match self {
+            ToplevelHandle::Wlr(h) => Some(h),
+            _ => None,
+        }
+    }
+
+    fn control(&self) -> Option<&dyn ToplevelControl> {
+        match self {
+            ToplevelHandle::Wlr(h) => Some(h),
+            ToplevelHandle::Ext(_) => None,
+            #[cfg(test)]
+            ToplevelHandle::Fake(h) => Some(&**h),
+        }
+    }
+}
+
+/// The window control requests, so that tests can check which ones are sent
+trait ToplevelControl {
+    fn version(&self) -> u32;
+    fn activate(&self, seat: &wl_seat::WlSeat);
+    fn close(&self);
+    fn set_minimized(&self, minimized: bool);
+    fn set_maximized(&self, maximized: bool);
+    fn set_fullscreen(&self, output: Option<&wl_output::WlOutput>);
+    fn unset_fullscreen(&self);
+    fn set_rectangle(&self, surface: &wl_surface::WlSurface, x: i32, y: i32, width: i32, height: i32);
+}
+
+impl ToplevelControl for toplevel_handle::ZwlrForeignToplevelHandleV1 {
+    fn version(&self) -> u32 {
+        self.as_ref().version()
+    }
+
+    fn activate(&self, seat: &wl_seat::WlSeat) {
+        toplevel_handle::ZwlrForeignToplevelHandleV1::activate(self, seat)
+    }
+
+    fn close(&self) {
+        toplevel_handle::ZwlrForeignToplevelHandleV1::close(self)
+    }
+
+    fn set_minimized(&self, minimized: bool) {
+        if minimized {
+            toplevel_handle::ZwlrForeignToplevelHandleV1::set_minimized(self)
+        } else {
+            self.unset_minimized()
+        }
+    }
+
+    fn set_maximized(&self, maximized: bool) {
+        if maximized {
+            toplevel_handle::ZwlrForeignToplevelHandleV1::set_maximized(self)
+        } else {
+            self.unset_maximized()
+        }
+    }
+
+    fn set_fullscreen(&self, output: Option<&wl_output::WlOutput>) {
+        toplevel_handle::ZwlrForeignToplevelHandleV1::set_fullscreen(self, output)
+    }
+
+    fn unset_fullscreen(&self) {
+        toplevel_handle::ZwlrForeignToplevelHandleV1::unset_fullscreen(self)
+    }
 
-impl Hash for ToplevelKey {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        Proxy::from(self.0.clone()).c_ptr().hash(state);
+    fn set_rectangle(&self, surface: &wl_surface::WlSurface, x: i32, y: i32, width: i32, height: i32) {
+        toplevel_handle::ZwlrForeignToplevelHandleV1::set_rectangle(self, surface, x, y, width, height)
+    }
+}
+
+/// Assigned in the order toplevels appear and never reused, so a message holding one
+/// can't end up pointing at a different window after the set of windows changes
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct ToplevelId(u64);
+
+/// Decoded from the protocol's state array
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct ToplevelFlags {
+    pub activated: bool,
+    pub maximized: bool,
+    pub minimized: bool,
+    pub fullscreen: bool,
+}
+
+impl ToplevelFlags {
+    /// The array is a list of native endian u32 state enum values
+    pub fn from_wire(state: &[u8]) -> ToplevelFlags {
+        let mut flags = ToplevelFlags::default();
+        for chunk in state.chunks_exact(4) {
+            match toplevel_handle::State::from_raw(u32::from_ne_bytes(chunk.try_into().unwrap())) {
+                Some(toplevel_handle::State::Activated) => flags.activated = true,
+                Some(toplevel_handle::State::Maximized) => flags.maximized = true,
+                Some(toplevel_handle::State::Minimized) => flags.minimized = true,
+                Some(toplevel_handle::State::Fullscreen) => flags.fullscreen = true,
+                _ => (),
+            }
+        }
+        flags
     }
 }
 
 #[derive(PartialEq, Clone)]
 pub struct ToplevelState {
-    pub handle: toplevel_handle::ZwlrForeignToplevelHandleV1,
+    pub id: ToplevelId,
+    pub handle: ToplevelHandle,
     pub title: String,
     pub app_id: String,
     pub gtk_app_id: Option<String>,
     pub outputs: Vec<wl_output::WlOutput>,
-    pub state: Vec<u8>,
+    pub state: ToplevelFlags,
+    /// The main window of a dialog
+    pub parent: Option<ToplevelId>,
+    /// When the toplevel last became activated, None if it never was
+    pub last_activated: Option<Instant>,
+    /// The compositor's identifier, unique for the session (only with ext-foreign-toplevel-list)
+    pub identifier: Option<String>,
 }
 
 impl ToplevelState {
     pub fn matches_id(&self, id: &str) -> bool {
         id == self.app_id || self.gtk_app_id.as_ref().map(|x| id == x).unwrap_or(false)
     }
+
+    /// Whether the window is (at least partly) on the output. Toplevels without any outputs
+    /// (not mapped yet, or listed by the ext backend which doesn't report them) count as being everywhere.
+    pub fn is_on_output(&self, output: &wl_output::WlOutput) -> bool {
+        self.outputs.is_empty() || self.outputs.contains(output)
+    }
+
+    fn new(id: ToplevelId, handle: ToplevelHandle) -> ToplevelState {
+        ToplevelState {
+            id,
+            handle,
+            title: "".to_owned(),
+            app_id: "".to_owned(),
+            gtk_app_id: None,
+            outputs: Vec::new(),
+            state: ToplevelFlags::default(),
+            parent: None,
+            last_activated: None,
+            identifier: None,
+        }
+    }
+
+    fn set_app_id(&mut self, app_id: &str) {
+        // Wayfire with option workarounds/app_id_mode == "full" adds gtk-shell id after a space
+        let mut words = app_id.split(' ');
+        self.app_id = words.next().unwrap_or("").to_owned();
+        self.gtk_app_id = words.next().map(|x| x.to_owned());
+        if words.next().is_some() {
+            eprintln!("WARN: app_id with more than one space: '{}'", app_id);
+        }
+    }
+}
+
+pub type ToplevelStates = HashMap<ToplevelId, ToplevelState>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToplevelOrder {
+    /// Oldest first
+    Creation,
+    /// Most recently activated first, then the never activated ones, newest first
+    RecentlyUsed,
+}
+
+/// The toplevels in a stable order (the map's own order changes between updates)
+pub fn sorted_toplevels(states: &ToplevelStates, order: ToplevelOrder) -> Vec<&ToplevelState> {
+    let mut sorted = states.values().collect::<Vec<_>>();
+    match order {
+        ToplevelOrder::Creation => sorted.sort_by_key(|t| t.id),
+        ToplevelOrder::RecentlyUsed => {
+            sorted.sort_by(|a, b| b.last_activated.cmp(&a.last_activated).then(b.id.cmp(&a.id)))
+        }
+    }
+    sorted
+}
+
+/// Toplevels whose parent is the given one (e.g. its dialogs), oldest first
+pub fn toplevel_children<'a>(
+    states: &'a ToplevelStates,
+    parent: &ToplevelState,
+) -> impl Iterator<Item = &'a ToplevelState> {
+    let id = parent.id;
+    sorted_toplevels(states, ToplevelOrder::Creation)
+        .into_iter()
+        .filter(move |t| t.parent == Some(id))
 }
 
-pub type ToplevelStates = HashMap<ToplevelKey, ToplevelState>;
+/// The parent, its parent and so on
+pub fn toplevel_ancestors<'a>(
+    states: &'a ToplevelStates,
+    topl: &'a ToplevelState,
+) -> impl Iterator<Item = &'a ToplevelState> {
+    // bounded in case of a buggy compositor sending a cycle
+    std::iter::successors(topl.parent.as_ref().and_then(|p| states.get(p)), move |t| {
+        t.parent.as_ref().and_then(|p| states.get(p))
+    })
+    .take(states.len())
+}
+
+/// The main window for a (possibly nested) dialog, or the toplevel itself
+pub fn toplevel_root<'a>(states: &'a ToplevelStates, topl: &'a ToplevelState) -> &'a ToplevelState {
+    toplevel_ancestors(states, topl).last().unwrap_or(topl)
+}
+
+/// The toplevels matching the filter in tree order, each one followed by its children, with the nesting depth.
+/// Toplevels with a parent that doesn't match the filter are roots, they are sorted by the given order.
+pub fn toplevel_tree<'a>(
+    states: &'a ToplevelStates,
+    order: ToplevelOrder,
+    filter: impl Fn(&ToplevelState) -> bool,
+) -> Vec<(&'a ToplevelState, usize)> {
+    fn visit<'a>(
+        states: &'a ToplevelStates,
+        filter: &dyn Fn(&ToplevelState) -> bool,
+        topl: &'a ToplevelState,
+        depth: usize,
+        out: &mut Vec<(&'a ToplevelState, usize)>,
+    ) {
+        if out.iter().any(|(t, _)| t.id == topl.id) {
+            return;
+        }
+        out.push((topl, depth));
+        for child in toplevel_children(states, topl).filter(|t| filter(t)) {
+            visit(states, filter, child, depth + 1, out);
+        }
+    }
+    let mut out = Vec::new();
+    for topl in sorted_toplevels(states, order).into_iter().filter(|t| filter(t)) {
+        let is_root = match topl.parent.as_ref().and_then(|p| states.get(p)) {
+            Some(parent) => !filter(parent),
+            None => true,
+        };
+        if is_root {
+            visit(states, &filter, topl, 0, &mut out);
+        }
+    }
+    out
+}
 
 pub struct ToplevelService {
     global: RefCell<Option<Attached<toplevel_manager::ZwlrForeignToplevelManagerV1>>>,
+    ext_global: RefCell<Option<Attached<ext_toplevel_list::ExtForeignToplevelListV1>>>,
     notifier: Rc<event_listener::Event>,
     states: Rc<RefCell<ToplevelStates>>,
+    /// Listed by the ext backend, shown only without the wlr one
+    ext_states: Rc<RefCell<ToplevelStates>>,
+    next_id: Rc<Cell<u64>>,
 }
 
 impl ToplevelService {
     pub fn new() -> ToplevelService {
         ToplevelService {
             global: RefCell::new(None),
+            ext_global: RefCell::new(None),
             notifier: Rc::new(event_listener::Event::new()),
             states: Rc::new(RefCell::new(HashMap::new())),
+            ext_states: Rc::new(RefCell::new(HashMap::new())),
+            next_id: Rc::new(Cell::new(0)),
         }
     }
 
     pub fn state(&self) -> Ref<'_, ToplevelStates> {
-        self.states.borrow()
+        self.active_states().borrow()
+    }
+
+    /// The toplevel with the given id, None if it's gone
+    pub fn get(&self, id: ToplevelId) -> Option<Ref<'_, ToplevelState>> {
+        Ref::filter_map(self.state(), |s| s.get(&id)).ok()
+    }
+
+    /// The ext-foreign-toplevel-list handle of the toplevel, for protocols that take one (like image capture).
+    /// With the wlr backend it's found by app id and title, so it's None when that's ambiguous.
+    pub fn ext_handle(&self, topl: &ToplevelState) -> Option<ext_toplevel_handle::ExtForeignToplevelHandleV1> {
+        let ext_states = self.ext_states.borrow();
+        let found = match topl.handle {
+            ToplevelHandle::Ext(_) => Some(topl),
+            _ => {
+                let mut matching = ext_states
+                    .values()
+                    .filter(|t| t.app_id == topl.app_id && t.gtk_app_id == topl.gtk_app_id && t.title == topl.title);
+                match (matching.next(), matching.next()) {
+                    (Some(t), None) => Some(t),
+                    _ => None,
+                }
+            }
+        };
+        match found.map(|t| &t.handle) {
+            Some(ToplevelHandle::Ext(h)) => Some(h.clone()),
+            _ => None,
+        }
     }
 
     pub fn subscribe(&self) -> impl Future<Output = ()> {
         self.notifier.listen()
     }
+
+    /// Whether window control (activating, closing etc.) is supported, the methods below do nothing otherwise
+    pub fn can_control(&self) -> bool {
+        self.global.borrow().is_some()
+    }
+
+    /// Activates the toplevel, then its dialogs so that they stay on top of it
+    pub fn activate(&self, topl: &ToplevelState, seat: &wl_seat::WlSeat) {
+        let handle = match topl.handle.control() {
+            Some(h) => h,
+            None => return,
+        };
+        handle.activate(seat);
+        let states = self.state();
+        let descendants = toplevel_tree(&states, ToplevelOrder::Creation, |t| {
+            toplevel_ancestors(&states, t).any(|a| a.id == topl.id)
+        });
+        for (child, _) in descendants {
+            if let Some(h) = child.handle.control() {
+                h.activate(seat);
+            }
+        }
+    }
+
+    /// Activates the toplevel with the given id, or minimizes it when it's already activated (like clicking it
+    /// in a window list). Does nothing if it's gone, e.g. when it closed since the list was shown.
+    pub fn activate_or_minimize(&self, id: ToplevelId, seat: &wl_seat::WlSeat) {
+        let topl = match self.get(id) {
+            Some(t) => t,
+            None => return,
+        };
+        if topl.state.activated {
+            self.set_minimized(&topl, true);
+        } else {
+            self.activate(&topl, seat);
+        }
+    }
+
+    /// Asks the app to close the window, it might not (e.g. to ask about unsaved changes first)
+    pub fn close(&self, topl: &ToplevelState) {
+        if let Some(h) = topl.handle.control() {
+            h.close();
+        }
+    }
+
+    pub fn set_minimized(&self, topl: &ToplevelState, minimized: bool) {
+        if let Some(h) = topl.handle.control() {
+            h.set_minimized(minimized);
+        }
+    }
+
+    pub fn set_maximized(&self, topl: &ToplevelState, maximized: bool) {
+        if let Some(h) = topl.handle.control() {
+            h.set_maximized(maximized);
+        }
+    }
+
+    /// Fullscreen on the given output, or wherever the compositor likes for None.
+    /// Ignored by compositors that don't support the protocol version with fullscreen.
+    pub fn set_fullscreen(&self, topl: &ToplevelState, output: Option<&wl_output::WlOutput>) {
+        match topl.handle.control() {
+            Some(h) if h.version() >= 2 => h.set_fullscreen(output),
+            _ => (),
+        }
+    }
+
+    pub fn unset_fullscreen(&self, topl: &ToplevelState) {
+        match topl.handle.control() {
+            Some(h) if h.version() >= 2 => h.unset_fullscreen(),
+            _ => (),
+        }
+    }
+
+    /// Tells the compositor where the window is represented on the given surface (e.g. a dock icon),
+    /// as a hint for minimize animations. A zero size rectangle removes the hint.
+    pub fn set_rectangle(
+        &self,
+        topl: &ToplevelState,
+        surface: &wl_surface::WlSurface,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    ) {
+        if let Some(h) = topl.handle.control() {
+            h.set_rectangle(surface, x, y, width, height);
+        }
+    }
+
+    fn next_id(&self) -> ToplevelId {
+        self.next_id.set(self.next_id.get() + 1);
+        ToplevelId(self.next_id.get())
+    }
+
+    fn active_states(&self) -> &RefCell<ToplevelStates> {
+        if self.global.borrow().is_some() {
+            &self.states
+        } else {
+            &self.ext_states
+        }
+    }
+
+    fn states_for(&self, topl: &ToplevelState) -> &RefCell<ToplevelStates> {
+        match topl.handle {
+            ToplevelHandle::Wlr(_) => &self.states,
+            ToplevelHandle::Ext(_) => &self.ext_states,
+            #[cfg(test)]
+            ToplevelHandle::Fake(_) => self.active_states(),
+        }
+    }
+
+    /// Applies a handle event, `at` is when it came in (for `last_activated`)
+    fn on_wlr_event(&self, topl: &mut ToplevelState, event: toplevel_handle::Event, at: Instant) {
+        match event {
+            toplevel_handle::Event::Title { title } => topl.title = title,
+            toplevel_handle::Event::AppId { app_id } => topl.set_app_id(&app_id),
+            toplevel_handle::Event::OutputEnter { output } => topl.outputs.push(output),
+            toplevel_handle::Event::OutputLeave { output } => topl.outputs.retain(|o| *o != output),
+            toplevel_handle::Event::State { state } => {
+                let state = ToplevelFlags::from_wire(&state);
+                if state.activated && !topl.state.activated {
+                    topl.last_activated = Some(at);
+                }
+                topl.state = state;
+            }
+            toplevel_handle::Event::Done => self.on_done(topl),
+            toplevel_handle::Event::Closed => self.on_closed(topl),
+            toplevel_handle::Event::Parent { parent } => {
+                topl.parent = parent.and_then(|p| p.as_ref().user_data().get::<ToplevelId>().copied())
+            }
+            x => eprintln!("Unknown toplevel event {:?}", x),
+        }
+    }
+
+    fn on_done(&self, topl: &ToplevelState) {
+        let mut sts = self.states_for(topl).borrow_mut();
+        if let None | Some(true) = sts.get(&topl.id).map(|v| topl != v) {
+            sts.insert(topl.id, topl.clone());
+            self.notifier.notify(usize::MAX);
+        }
+    }
+
+    fn on_closed(&self, topl: &ToplevelState) {
+        self.states_for(topl).borrow_mut().remove(&topl.id);
+        self.notifier.notify(usize::MAX);
+    }
 }
 
+/// Registered for the wlr protocol, preferred over the ext one since it can also control windows
 pub struct ToplevelServiceRc(pub Rc<ToplevelService>);
 
 impl GlobalHandler<toplevel_manager::ZwlrForeignToplevelManagerV1> for ToplevelServiceRc {
     fn created(&mut self, registry: Attached<wl_registry::WlRegistry>, id: u32, version: u32, _: DispatchData) {
         let main = registry.bind::<toplevel_manager::ZwlrForeignToplevelManagerV1>(version, id);
-        let states = self.0.states.clone();
-        let notifier = self.0.notifier.clone();
+        let service = self.0.clone();
         main.quick_assign(move |_, event, _| match event {
             toplevel_manager::Event::Toplevel { toplevel } => {
-                let mut topl = ToplevelState {
-                    handle: toplevel.detach(),
-                    title: "".to_owned(),
-                    app_id: "".to_owned(),
-                    gtk_app_id: None,
-                    outputs: Vec::new(),
-                    state: Vec::new(),
-                };
-                let states = states.clone();
-                let notifier = notifier.clone();
-                toplevel.quick_assign(move |_, event, _| match event {
-                    toplevel_handle::Event::Title { title } => topl.title = title,
-                    toplevel_handle::Event::AppId { app_id } => {
-                        // Wayfire with option workarounds/app_id_mode == "full" adds gtk-shell id after a space
-                        let mut words = app_id.split(' ');
-                        topl.app_id = words.next().unwrap_or("").to_owned();
-                        topl.gtk_app_id = words.next().map(|x| x.to_owned());
-                        if words.next().is_some() {
-                            eprintln!("WARN: app_id with more than one space: '{}'", app_id);
-                        }
-                    }
-                    toplevel_handle::Event::OutputEnter { output } => topl.outputs.push(output),
-                    toplevel_handle::Event::OutputLeave { output } => topl.outputs.retain(|o| *o != output),
-                    toplevel_handle::Event::State { state } => topl.state = state,
-                    toplevel_handle::Event::Done => {
-                        let mut sts = states.borrow_mut();
-                        let k = ToplevelKey(topl.handle.clone());
-                        if let None | Some(true) = sts.get(&k).map(|v| topl != *v) {
-                            sts.insert(k, topl.clone());
-                            notifier.notify(usize::MAX);
-                        }
-                    }
-                    toplevel_handle::Event::Closed => {
-                        states.borrow_mut().remove(&ToplevelKey(topl.handle.clone()));
-                        notifier.notify(usize::MAX);
-                    }
-                    toplevel_handle::Event::Parent { .. } => {}
-                    x => panic!("Unknown toplevel event {:?}", x),
-                });
+                let id = service.next_id();
+                // for finding the id when the handle comes back as a parent
+                toplevel.as_ref().user_data().set(move || id);
+                let mut topl = ToplevelState::new(id, ToplevelHandle::Wlr(toplevel.detach()));
+                let service = service.clone();
+                toplevel.quick_assign(move |_, event, _| service.on_wlr_event(&mut topl, event, Instant::now()));
+            }
+            toplevel_manager::Event::Finished => {
+                eprintln!("The compositor stopped sending toplevels");
+                *service.global.borrow_mut() = None;
+                service.notifier.notify(usize::MAX);
             }
-            x => panic!("Unknown toplevel manager event {:?}", x),
+            x => eprintln!("Unknown toplevel manager event {:?}", x),
         });
-        *self.0.global.borrow_mut() = Some((*main).clone())
+        *self.0.global.borrow_mut() = Some((*main).clone());
+        // switching from the ext backend
+        self.0.notifier.notify(usize::MAX);
     }
 
     fn get(&self) -> Option<Attached<toplevel_manager::ZwlrForeignToplevelManagerV1>> {
@@ -129,6 +513,49 @@ impl GlobalHandler<toplevel_manager::ZwlrForeignToplevelManagerV1> for ToplevelS
     }
 }
 
+/// Registered for the ext protocol, whose toplevels are shown when the wlr one isn't available.
+/// Also kept alongside the wlr one, for the protocols that take ext handles (see `ToplevelService::ext_handle`).
+pub struct ExtToplevelListRc(pub Rc<ToplevelService>);
+
+impl GlobalHandler<ext_toplevel_list::ExtForeignToplevelListV1> for ExtToplevelListRc {
+    fn created(&mut self, registry: Attached<wl_registry::WlRegistry>, id: u32, version: u32, _: DispatchData) {
+        let main = registry.bind::<ext_toplevel_list::ExtForeignToplevelListV1>(version, id);
+        let service = self.0.clone();
+        main.quick_assign(move |list, event, _| match event {
+            ext_toplevel_list::Event::Toplevel { toplevel } => {
+                let mut topl = ToplevelState::new(service.next_id(), ToplevelHandle::Ext(toplevel.detach()));
+                let service = service.clone();
+                toplevel.quick_assign(move |toplevel, event, _| match event {
+                    ext_toplevel_handle::Event::Title { title } => topl.title = title,
+                    ext_toplevel_handle::Event::AppId { app_id } => topl.set_app_id(&app_id),
+                    ext_toplevel_handle::Event::Identifier { identifier } => topl.identifier = Some(identifier),
+                    ext_toplevel_handle::Event::Done => service.on_done(&topl),
+                    ext_toplevel_handle::Event::Closed => {
+                        service.on_closed(&topl);
+                        toplevel.destroy();
+                    }
+                    x => eprintln!("Unknown toplevel event {:?}", x),
+                });
+            }
+            ext_toplevel_list::Event::Finished => {
+                eprintln!("The compositor stopped sending ext toplevels");
+                list.destroy();
+                *service.ext_global.borrow_mut() = None;
+                if service.global.borrow().is_none() {
+                    service.ext_states.borrow_mut().clear();
+                }
+                service.notifier.notify(usize::MAX);
+            }
+            x => eprintln!("Unknown toplevel list event {:?}", x),
+        });
+        *self.0.ext_global.borrow_mut() = Some((*main).clone())
+    }
+
+    fn get(&self) -> Option<Attached<ext_toplevel_list::ExtForeignToplevelListV1>> {
+        self.0.ext_global.borrow().clone()
+    }
+}
+
 pub trait HasToplevelService {
     fn toplevel_service(&self) -> Rc<ToplevelService>;
 }
@@ -148,3 +575,367 @@ macro_rules! toplevel_handler {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use wayland_client::Proxy;
+
+    #[derive(Debug, Default, PartialEq, Eq)]
+    pub struct FakeHandle {
+        pub version: u32,
+        pub requests: RefCell<Vec<Request>>,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub enum Request {
+        Activate,
+        Close,
+        SetMinimized(bool),
+        SetMaximized(bool),
+        SetFullscreen,
+        UnsetFullscreen,
+        SetRectangle(i32, i32, i32, i32),
+    }
+
+    impl FakeHandle {
+        fn take(&self) -> Vec<Request> {
+            self.requests.take()
+        }
+    }
+
+    impl ToplevelControl for FakeHandle {
+        fn version(&self) -> u32 {
+            self.version
+        }
+
+        fn activate(&self, _: &wl_seat::WlSeat) {
+            self.requests.borrow_mut().push(Request::Activate)
+        }
+
+        fn close(&self) {
+            self.requests.borrow_mut().push(Request::Close)
+        }
+
+        fn set_minimized(&self, minimized: bool) {
+            self.requests.borrow_mut().push(Request::SetMinimized(minimized))
+        }
+
+        fn set_maximized(&self, maximized: bool) {
+            self.requests.borrow_mut().push(Request::SetMaximized(maximized))
+        }
+
+        fn set_fullscreen(&self, _: Option<&wl_output::WlOutput>) {
+            self.requests.borrow_mut().push(Request::SetFullscreen)
+        }
+
+        fn unset_fullscreen(&self) {
+            self.requests.borrow_mut().push(Request::UnsetFullscreen)
+        }
+
+        fn set_rectangle(&self, _: &wl_surface::WlSurface, x: i32, y: i32, width: i32, height: i32) {
+            self.requests
+                .borrow_mut()
+                .push(Request::SetRectangle(x, y, width, height))
+        }
+    }
+
+    /// A proxy that isn't connected to anything, requests on it are ignored
+    fn dead<I: wayland_client::Interface + From<Proxy<I>> + AsRef<Proxy<I>>>() -> I {
+        unsafe { Proxy::<I>::from_c_ptr(std::ptr::null_mut()) }.into()
+    }
+
+    fn fake_toplevel(service: &ToplevelService, version: u32) -> (ToplevelState, Rc<FakeHandle>) {
+        let handle = Rc::new(FakeHandle {
+            version,
+            ..FakeHandle::default()
+        });
+        let topl = ToplevelState::new(service.next_id(), ToplevelHandle::Fake(handle.clone()));
+        (topl, handle)
+    }
+
+    fn wire(states: &[toplevel_handle::State]) -> Vec<u8> {
+        states.iter().flat_map(|s| s.to_raw().to_ne_bytes()).collect()
+    }
+
+    /// Events other than state changes don't care about the time
+    fn send(service: &ToplevelService, topl: &mut ToplevelState, event: toplevel_handle::Event) {
+        service.on_wlr_event(topl, event, Instant::now());
+    }
+
+    fn set_state(service: &ToplevelService, topl: &mut ToplevelState, states: &[toplevel_handle::State], at: Instant) {
+        service.on_wlr_event(topl, toplevel_handle::Event::State { state: wire(states) }, at);
+        service.on_wlr_event(topl, toplevel_handle::Event::Done, at);
+    }
+
+    #[test]
+    fn flags_from_wire() {
+        assert_eq!(ToplevelFlags::from_wire(&[]), ToplevelFlags::default());
+        assert_eq!(
+            ToplevelFlags::from_wire(&wire(&[
+                toplevel_handle::State::Activated,
+                toplevel_handle::State::Fullscreen
+            ])),
+            ToplevelFlags {
+                activated: true,
+                fullscreen: true,
+                ..ToplevelFlags::default()
+            }
+        );
+        assert_eq!(
+            ToplevelFlags::from_wire(&wire(&[
+                toplevel_handle::State::Maximized,
+                toplevel_handle::State::Minimized
+            ])),
+            ToplevelFlags {
+                maximized: true,
+                minimized: true,
+                ..ToplevelFlags::default()
+            }
+        );
+    }
+
+    #[test]
+    fn flags_from_wire_skips_unknown_and_partial_values() {
+        let mut state = 1234u32.to_ne_bytes().to_vec();
+        state.extend(wire(&[toplevel_handle::State::Minimized]));
+        state.extend([2, 0]);
+        assert_eq!(
+            ToplevelFlags::from_wire(&state),
+            ToplevelFlags {
+                minimized: true,
+                ..ToplevelFlags::default()
+            }
+        );
+    }
+
+    #[test]
+    fn published_on_done() {
+        let service = ToplevelService::new();
+        let (mut topl, _) = fake_toplevel(&service, 1);
+        send(
+            &service,
+            &mut topl,
+            toplevel_handle::Event::Title {
+                title: "Files".to_owned(),
+            },
+        );
+        send(
+            &service,
+            &mut topl,
+            toplevel_handle::Event::AppId {
+                app_id: "org.gnome.Nautilus nautilus".to_owned(),
+            },
+        );
+        assert!(service.get(topl.id).is_none());
+
+        send(&service, &mut topl, toplevel_handle::Event::Done);
+        let published = service.get(topl.id).unwrap();
+        assert_eq!(published.title, "Files");
+        assert_eq!(published.app_id, "org.gnome.Nautilus");
+        assert_eq!(published.gtk_app_id.as_deref(), Some("nautilus"));
+        assert!(published.matches_id("nautilus"));
+    }
+
+    #[test]
+    fn state_transitions() {
+        let service = ToplevelService::new();
+        let (mut topl, _) = fake_toplevel(&service, 1);
+        let start = Instant::now();
+        send(&service, &mut topl, toplevel_handle::Event::Done);
+        assert_eq!(service.get(topl.id).unwrap().last_activated, None);
+
+        set_state(&service, &mut topl, &[toplevel_handle::State::Activated], start);
+        let activated = service.get(topl.id).unwrap().last_activated;
+        assert_eq!(activated, Some(start));
+        assert!(service.get(topl.id).unwrap().state.activated);
+
+        // staying activated doesn't count as activating again
+        set_state(
+            &service,
+            &mut topl,
+            &[toplevel_handle::State::Activated, toplevel_handle::State::Maximized],
+            start + Duration::from_secs(1),
+        );
+        assert_eq!(service.get(topl.id).unwrap().last_activated, activated);
+        assert!(service.get(topl.id).unwrap().state.maximized);
+
+        set_state(
+            &service,
+            &mut topl,
+            &[toplevel_handle::State::Minimized],
+            start + Duration::from_secs(2),
+        );
+        let published = service.get(topl.id).unwrap().clone();
+        assert_eq!(
+            published.state,
+            ToplevelFlags {
+                minimized: true,
+                ..ToplevelFlags::default()
+            }
+        );
+        assert_eq!(published.last_activated, activated);
+
+        set_state(
+            &service,
+            &mut topl,
+            &[toplevel_handle::State::Activated],
+            start + Duration::from_secs(3),
+        );
+        assert_eq!(
+            service.get(topl.id).unwrap().last_activated,
+            Some(start + Duration::from_secs(3))
+        );
+
+        send(&service, &mut topl, toplevel_handle::Event::Closed);
+        assert!(service.get(topl.id).is_none());
+        assert!(service.state().is_empty());
+    }
+
+    #[test]
+    fn window_control() {
+        let service = ToplevelService::new();
+        let (topl, handle) = fake_toplevel(&service, 1);
+        service.set_minimized(&topl, true);
+        service.set_minimized(&topl, false);
+        service.set_maximized(&topl, true);
+        service.set_maximized(&topl, false);
+        service.close(&topl);
+        service.set_rectangle(&topl, &dead(), 1, 2, 3, 4);
+        assert_eq!(
+            handle.take(),
+            vec![
+                Request::SetMinimized(true),
+                Request::SetMinimized(false),
+                Request::SetMaximized(true),
+                Request::SetMaximized(false),
+                Request::Close,
+                Request::SetRectangle(1, 2, 3, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn fullscreen_needs_version_2() {
+        let service = ToplevelService::new();
+        let (old, old_handle) = fake_toplevel(&service, 1);
+        service.set_fullscreen(&old, None);
+        service.unset_fullscreen(&old);
+        assert_eq!(old_handle.take(), vec![]);
+
+        let (new, new_handle) = fake_toplevel(&service, 2);
+        service.set_fullscreen(&new, Some(&dead()));
+        service.unset_fullscreen(&new);
+        assert_eq!(
+            new_handle.take(),
+            vec![Request::SetFullscreen, Request::UnsetFullscreen]
+        );
+    }
+
+    #[test]
+    fn activate_brings_dialogs_along() {
+        let service = ToplevelService::new();
+        let seat = dead();
+        let (main, main_handle) = fake_toplevel(&service, 1);
+        let (mut dialog, dialog_handle) = fake_toplevel(&service, 1);
+        let (mut nested, nested_handle) = fake_toplevel(&service, 1);
+        let (other, other_handle) = fake_toplevel(&service, 1);
+        dialog.parent = Some(main.id);
+        nested.parent = Some(dialog.id);
+        for topl in [&main, &dialog, &nested, &other] {
+            service.on_done(topl);
+        }
+
+        service.activate(&main, &seat);
+        assert_eq!(main_handle.take(), vec![Request::Activate]);
+        assert_eq!(dialog_handle.take(), vec![Request::Activate]);
+        assert_eq!(nested_handle.take(), vec![Request::Activate]);
+        assert_eq!(other_handle.take(), vec![]);
+
+        service.activate(&dialog, &seat);
+        assert_eq!(main_handle.take(), vec![]);
+        assert_eq!(dialog_handle.take(), vec![Request::Activate]);
+        assert_eq!(nested_handle.take(), vec![Request::Activate]);
+    }
+
+    /// A window list made from one set of toplevels, clicked after the set changed
+    #[test]
+    fn clicks_target_the_listed_window() {
+        let service = ToplevelService::new();
+        let seat = dead();
+        let (first, first_handle) = fake_toplevel(&service, 1);
+        let (second, second_handle) = fake_toplevel(&service, 1);
+        service.on_done(&first);
+        service.on_done(&second);
+        let listed = toplevel_tree(&service.state(), ToplevelOrder::Creation, |_| true)
+            .into_iter()
+            .map(|(t, _)| t.id)
+            .collect::<Vec<_>>();
+        let clicked = listed[1];
+
+        // the first one closes and a new one takes the second place in the list
+        service.on_closed(&first);
+        let (third, third_handle) = fake_toplevel(&service, 1);
+        service.on_done(&third);
+        assert_eq!(
+            toplevel_tree(&service.state(), ToplevelOrder::Creation, |_| true)[1]
+                .0
+                .id,
+            third.id
+        );
+
+        service.activate_or_minimize(clicked, &seat);
+        assert_eq!(second_handle.take(), vec![Request::Activate]);
+        assert_eq!(third_handle.take(), vec![]);
+        assert_eq!(first_handle.take(), vec![]);
+
+        // then the clicked one closes
+        service.on_closed(&second);
+        service.activate_or_minimize(clicked, &seat);
+        assert_eq!(second_handle.take(), vec![]);
+        assert_eq!(third_handle.take(), vec![]);
+    }
+
+    #[test]
+    fn clicks_follow_the_window_when_reordered() {
+        let service = ToplevelService::new();
+        let seat = dead();
+        let (mut first, first_handle) = fake_toplevel(&service, 1);
+        let (mut second, second_handle) = fake_toplevel(&service, 1);
+        let start = Instant::now();
+        set_state(&service, &mut first, &[toplevel_handle::State::Activated], start);
+        set_state(&service, &mut second, &[], start);
+        let clicked = sorted_toplevels(&service.state(), ToplevelOrder::RecentlyUsed)[0].id;
+        assert_eq!(clicked, first.id);
+
+        // switching to the other one moves it to the front of the list
+        let later = start + Duration::from_secs(1);
+        set_state(&service, &mut first, &[], later);
+        set_state(&service, &mut second, &[toplevel_handle::State::Activated], later);
+        assert_eq!(
+            sorted_toplevels(&service.state(), ToplevelOrder::RecentlyUsed)[0].id,
+            second.id
+        );
+
+        service.activate_or_minimize(clicked, &seat);
+        assert_eq!(first_handle.take(), vec![Request::Activate]);
+        assert_eq!(second_handle.take(), vec![]);
+
+        // clicking the one in front minimizes it
+        service.activate_or_minimize(second.id, &seat);
+        assert_eq!(second_handle.take(), vec![Request::SetMinimized(true)]);
+        assert_eq!(first_handle.take(), vec![]);
+    }
+
+    #[test]
+    fn no_control_without_wlr() {
+        let service = ToplevelService::new();
+        let topl = ToplevelState::new(service.next_id(), ToplevelHandle::Ext(dead()));
+        // nothing to send the requests to, they're dropped
+        service.activate(&topl, &dead());
+        service.close(&topl);
+        service.set_minimized(&topl, true);
+        assert!(!service.can_control());
+    }
+}
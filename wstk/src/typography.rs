@@ -0,0 +1,77 @@
+//! Text sizes and fonts from the theme, scaled by the desktop text scaling factor.
+//! Views should use these instead of fixed pixel sizes, so that text follows the settings.
+
+use iced_graphics::font::{Family, Source};
+use iced_native::{text, widget::Text, Font};
+use std::{cell::RefCell, collections::HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextSize {
+    /// Lists, tooltips
+    Small,
+    Body,
+    /// Dialog contents
+    Large,
+    Title,
+}
+
+/// The size in pixels for the current theme
+pub fn text_size(size: TextSize) -> u16 {
+    crate::theme().typography.size(size)
+}
+
+/// A Text widget of the given size
+pub fn text<Renderer: text::Renderer>(content: impl Into<String>, size: TextSize) -> Text<Renderer> {
+    Text::new(content).size(text_size(size))
+}
+
+thread_local! {
+    // fonts live as long as the process, iced wants &'static data
+    static LOADED: RefCell<HashMap<String, Option<(&'static str, &'static [u8])>>> = RefCell::new(HashMap::new());
+}
+
+/// Finds a system font by family name
+pub(crate) fn load_font(family: &str) -> Option<(&'static str, &'static [u8])> {
+    LOADED.with(|l| {
+        l.borrow_mut()
+            .entry(family.to_owned())
+            .or_insert_with(|| match Source::new().load(&[Family::Title(family.to_owned())]) {
+                Ok(bytes) => Some((
+                    Box::leak(family.to_owned().into_boxed_str()),
+                    Box::leak(bytes.into_boxed_slice()),
+                )),
+                Err(e) => {
+                    eprintln!("Could not load font {}: {:?}", family, e);
+                    None
+                }
+            })
+            .clone()
+    })
+}
+
+/// The interface font from the theme as the renderer's default font (None for the built-in default)
+pub(crate) fn default_font_bytes() -> Option<&'static [u8]> {
+    crate::theme()
+        .fonts
+        .interface
+        .as_deref()
+        .and_then(load_font)
+        .map(|(_, bytes)| bytes)
+}
+
+/// The monospace font from the theme, loaded by the renderer the first time it draws with it.
+/// Falls back to the default font when there's none or it can't be found.
+pub fn monospace_font() -> Font {
+    match crate::theme().fonts.monospace.as_deref().and_then(load_font) {
+        Some((name, bytes)) => Font::External { name, bytes },
+        None => Font::Default,
+    }
+}
+
+/// A Text widget of the given size in the monospace font
+pub fn monospace_text<Renderer: text::Renderer<Font = Font>>(
+    content: impl Into<String>,
+    size: TextSize,
+) -> Text<Renderer> {
+    text(content, size).font(monospace_font())
+}
@@ -1,8 +1,29 @@
+pub mod accessible;
+pub use accessible::Accessible;
+
 pub mod addeventlistener;
 pub use addeventlistener::*;
 
+pub mod contextmenu;
+pub use contextmenu::{ContextMenu, MenuItem};
+
+pub mod dragsource;
+pub use dragsource::DragSource;
+
+pub mod droptarget;
+pub use droptarget::DropTarget;
+
+pub mod focusable;
+pub use focusable::Focusable;
+
 pub mod getregion;
 pub use getregion::*;
 
+pub mod inputregion;
+pub use inputregion::InputRegion;
+
 pub mod prim;
 pub use prim::*;
+
+pub mod tooltip;
+pub use tooltip::Tooltip;
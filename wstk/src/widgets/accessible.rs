@@ -0,0 +1,147 @@
+//! Describes the content to assistive technologies (see the a11y module).
+//! Accessibles drawn inside the content become its children in the tree.
+
+use crate::a11y::{self, Node, Role};
+use iced_native::*;
+
+pub struct Accessible<'a, Message, Renderer: self::Renderer> {
+    content: Element<'a, Message, Renderer>,
+    role: Role,
+    name: String,
+    description: String,
+    checked: Option<bool>,
+    focused: Option<bool>,
+    action: Option<Option<String>>,
+}
+
+impl<'a, Message, Renderer> Accessible<'a, Message, Renderer>
+where
+    Renderer: self::Renderer,
+{
+    pub fn new<T>(content: T, role: Role, name: impl Into<String>) -> Self
+    where
+        T: Into<Element<'a, Message, Renderer>>,
+    {
+        Accessible {
+            content: content.into(),
+            role,
+            name: name.into(),
+            description: String::new(),
+            checked: None,
+            focused: None,
+            action: None,
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Makes the widget checkable, e.g. for toggle buttons and check boxes
+    pub fn checked(mut self, checked: bool) -> Self {
+        self.checked = Some(checked);
+        self
+    }
+
+    /// Overrides the focus state, for widgets that track focus on their own (e.g. text inputs).
+    /// By default, the widget is focused when a Focusable with the same bounds is.
+    pub fn focused(mut self, focused: bool) -> Self {
+        self.focused = Some(focused);
+        self
+    }
+
+    /// Sets the name of the default action (performed by clicking the center of the widget),
+    /// by default button-like roles have a "click" action
+    pub fn action(mut self, action: Option<String>) -> Self {
+        self.action = Some(action);
+        self
+    }
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for Accessible<'a, Message, Renderer>
+where
+    Renderer: self::Renderer,
+    Message: Clone,
+{
+    fn width(&self) -> Length {
+        self.content.width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let content = self.content.layout(renderer, &limits);
+        let size = limits.resolve(content.size());
+        layout::Node::with_children(size, vec![content])
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) -> event::Status {
+        self.content.on_event(
+            event,
+            layout.children().next().unwrap(),
+            cursor_position,
+            renderer,
+            clipboard,
+            shell,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content
+            .mouse_interaction(layout.children().next().unwrap(), cursor_position, viewport, renderer)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let mut node = Node::new(self.role, self.name.clone(), bounds);
+        node.description = self.description.clone();
+        node.checked = self.checked;
+        node.focused = self.focused.unwrap_or_else(|| super::focusable::is_focused(bounds));
+        if let Some(ref action) = self.action {
+            node.action = action.clone();
+        }
+        a11y::begin_node(node);
+        self.content.draw(
+            renderer,
+            style,
+            layout.children().next().unwrap(),
+            cursor_position,
+            viewport,
+        );
+        a11y::end_node();
+    }
+}
+
+impl<'a, Message, Renderer> From<Accessible<'a, Message, Renderer>> for Element<'a, Message, Renderer>
+where
+    Renderer: 'a + self::Renderer,
+    Message: 'a + Clone,
+{
+    fn from(x: Accessible<'a, Message, Renderer>) -> Element<'a, Message, Renderer> {
+        Element::new(x)
+    }
+}
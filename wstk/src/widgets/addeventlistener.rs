@@ -2,10 +2,18 @@
 //! the equivalent of anything.addEventListener('mouseover', ..) :P
 
 use iced_native::*;
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+pub const DOUBLE_CLICK_DISTANCE: f32 = 4.0;
+pub const LONG_PRESS_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct State {
     is_hovered: bool,
+    press: Option<(mouse::Button, Instant)>,
+    long_pressed: bool,
+    last_click: Option<(Instant, Point)>,
 }
 
 pub struct AddEventListener<'a, Message, Renderer: self::Renderer> {
@@ -13,6 +21,14 @@ pub struct AddEventListener<'a, Message, Renderer: self::Renderer> {
     content: Element<'a, Message, Renderer>,
     pointer_enter: Option<Message>,
     pointer_leave: Option<Message>,
+    press: Vec<(mouse::Button, Message)>,
+    release: Vec<(mouse::Button, Message)>,
+    scroll: Option<Box<dyn Fn(mouse::ScrollDelta) -> Message + 'a>>,
+    long_press: Option<Message>,
+    long_press_delay: Duration,
+    double_click: Option<Message>,
+    key_press: Vec<(keyboard::KeyCode, Message)>,
+    modifiers_change: Option<Box<dyn Fn(keyboard::Modifiers) -> Message + 'a>>,
 }
 
 impl<'a, Message, Renderer> AddEventListener<'a, Message, Renderer>
@@ -28,6 +44,14 @@ where
             content: content.into(),
             pointer_enter: None,
             pointer_leave: None,
+            press: Vec::new(),
+            release: Vec::new(),
+            scroll: None,
+            long_press: None,
+            long_press_delay: LONG_PRESS_DELAY,
+            double_click: None,
+            key_press: Vec::new(),
+            modifiers_change: None,
         }
     }
 
@@ -40,6 +64,52 @@ where
         self.pointer_leave = Some(msg);
         self
     }
+
+    pub fn on_press(mut self, button: mouse::Button, msg: Message) -> Self {
+        self.press.push((button, msg));
+        self
+    }
+
+    /// Only fires when the press also happened over the widget, and was not a long press
+    pub fn on_release(mut self, button: mouse::Button, msg: Message) -> Self {
+        self.release.push((button, msg));
+        self
+    }
+
+    pub fn on_scroll(mut self, f: impl Fn(mouse::ScrollDelta) -> Message + 'a) -> Self {
+        self.scroll = Some(Box::new(f));
+        self
+    }
+
+    /// Left button (or touch) held for `long_press_delay`.
+    pub fn on_long_press(mut self, msg: Message) -> Self {
+        self.long_press = Some(msg);
+        self
+    }
+
+    pub fn long_press_delay(mut self, delay: Duration) -> Self {
+        self.long_press_delay = delay;
+        self
+    }
+
+    pub fn on_double_click(mut self, msg: Message) -> Self {
+        self.double_click = Some(msg);
+        self
+    }
+
+    /// Key pressed while the surface has keyboard focus, wherever the pointer is.
+    /// Only fires if the content did not handle the key (e.g. typing in a text input).
+    pub fn on_key_press(mut self, key_code: keyboard::KeyCode, msg: Message) -> Self {
+        self.key_press.push((key_code, msg));
+        self
+    }
+
+    /// Shift/Ctrl/Alt/Logo pressed or released while the surface has keyboard focus,
+    /// also sent with the current state when the surface gets the focus
+    pub fn on_modifiers_change(mut self, f: impl Fn(keyboard::Modifiers) -> Message + 'a) -> Self {
+        self.modifiers_change = Some(Box::new(f));
+        self
+    }
 }
 
 impl<'a, Message, Renderer> Widget<Message, Renderer> for AddEventListener<'a, Message, Renderer>
@@ -84,14 +154,89 @@ where
         }
         self.state.is_hovered = is_mouse_over;
 
-        self.content.on_event(
-            event,
+        let now = Instant::now();
+        match &event {
+            &Event::Mouse(mouse::Event::ButtonPressed(button)) if is_mouse_over => {
+                self.state.press = Some((button, now));
+                self.state.long_pressed = false;
+                if button == mouse::Button::Left && self.long_press.is_some() {
+                    crate::request_redraw_at(now + self.long_press_delay);
+                }
+                for (_, msg) in self.press.iter().filter(|(b, _)| *b == button) {
+                    shell.publish(msg.clone());
+                }
+                if button == mouse::Button::Left {
+                    match self.state.last_click {
+                        Some((time, pos))
+                            if now.duration_since(time) <= DOUBLE_CLICK_INTERVAL
+                                && pos.distance(cursor_position) <= DOUBLE_CLICK_DISTANCE =>
+                        {
+                            if let Some(ref msg) = self.double_click {
+                                shell.publish(msg.clone());
+                            }
+                            self.state.last_click = None;
+                        }
+                        _ => self.state.last_click = Some((now, cursor_position)),
+                    }
+                }
+            }
+            &Event::Mouse(mouse::Event::ButtonReleased(button)) => {
+                if let Some((pressed, time)) = self.state.press {
+                    if pressed == button {
+                        self.state.press = None;
+                        if !self.state.long_pressed
+                            && button == mouse::Button::Left
+                            && now.duration_since(time) >= self.long_press_delay
+                        {
+                            if let Some(ref msg) = self.long_press {
+                                shell.publish(msg.clone());
+                                self.state.long_pressed = true;
+                            }
+                        }
+                        if is_mouse_over && !self.state.long_pressed {
+                            for (_, msg) in self.release.iter().filter(|(b, _)| *b == button) {
+                                shell.publish(msg.clone());
+                            }
+                        }
+                    }
+                }
+            }
+            &Event::Mouse(mouse::Event::WheelScrolled { delta }) if is_mouse_over => {
+                if let Some(ref f) = self.scroll {
+                    shell.publish(f(delta));
+                }
+            }
+            &Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                if let Some(ref f) = self.modifiers_change {
+                    shell.publish(f(modifiers));
+                }
+            }
+            _ => (),
+        }
+        if let Some((mouse::Button::Left, time)) = self.state.press {
+            if !self.state.long_pressed && now.duration_since(time) >= self.long_press_delay {
+                if let Some(ref msg) = self.long_press {
+                    shell.publish(msg.clone());
+                    self.state.long_pressed = true;
+                }
+            }
+        }
+
+        let status = self.content.on_event(
+            event.clone(),
             layout.children().next().unwrap(),
             cursor_position,
             renderer,
             clipboard,
             shell,
-        )
+        );
+        if let (Event::Keyboard(keyboard::Event::KeyPressed { key_code, .. }), event::Status::Ignored) = (event, status)
+        {
+            for (_, msg) in self.key_press.iter().filter(|(k, _)| *k == key_code) {
+                shell.publish(msg.clone());
+            }
+        }
+        status
     }
 
     fn mouse_interaction(
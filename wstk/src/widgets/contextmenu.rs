@@ -0,0 +1,575 @@
+//! Popup menus opened with a right click on any widget
+
+use crate::a11y::{self, Node, Role};
+use iced_native::{
+    widget::{button, container},
+    *,
+};
+use std::cell::Cell;
+
+pub const ITEM_PADDING: f32 = 6.0;
+pub const PANEL_PADDING: f32 = 4.0;
+pub const SEPARATOR_HEIGHT: f32 = 9.0;
+pub const INDICATOR_WIDTH: f32 = 16.0;
+
+pub enum MenuItem<Message> {
+    Action {
+        label: String,
+        msg: Message,
+    },
+    Check {
+        label: String,
+        checked: bool,
+        msg: Message,
+    },
+    Submenu {
+        label: String,
+        items: Vec<MenuItem<Message>>,
+    },
+    Separator,
+}
+
+impl<Message> MenuItem<Message> {
+    pub fn action(label: impl Into<String>, msg: Message) -> Self {
+        MenuItem::Action {
+            label: label.into(),
+            msg,
+        }
+    }
+
+    pub fn check(label: impl Into<String>, checked: bool, msg: Message) -> Self {
+        MenuItem::Check {
+            label: label.into(),
+            checked,
+            msg,
+        }
+    }
+
+    pub fn submenu(label: impl Into<String>, items: Vec<MenuItem<Message>>) -> Self {
+        MenuItem::Submenu {
+            label: label.into(),
+            items,
+        }
+    }
+
+    fn label(&self) -> Option<&str> {
+        match self {
+            MenuItem::Action { label, .. } | MenuItem::Check { label, .. } | MenuItem::Submenu { label, .. } => {
+                Some(label)
+            }
+            MenuItem::Separator => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct State {
+    open_at: Option<Point>,
+    // Highlighted item of each open (sub)menu, the last one being the innermost
+    path: Vec<Option<usize>>,
+    // The release of the button that opened the menu should not activate anything
+    ignore_release: bool,
+    // on_event doesn't get the viewport, but hit testing needs the same (clamped) panel positions
+    last_viewport: Cell<Rectangle>,
+}
+
+impl State {
+    pub fn is_open(&self) -> bool {
+        self.open_at.is_some()
+    }
+
+    pub fn close(&mut self) {
+        self.open_at = None;
+        self.path.clear();
+    }
+}
+
+struct Panel {
+    bounds: Rectangle,
+    items: Vec<Rectangle>,
+}
+
+pub struct ContextMenu<'a, Message, Renderer: text::Renderer> {
+    state: &'a mut State,
+    content: Element<'a, Message, Renderer>,
+    items: Vec<MenuItem<Message>>,
+    text_size: u16,
+    panel_style: container::Style,
+    item_style: button::Style,
+    item_hovered_style: button::Style,
+}
+
+impl<'a, Message, Renderer> ContextMenu<'a, Message, Renderer>
+where
+    Renderer: text::Renderer,
+    Message: Clone,
+{
+    pub fn new<T>(state: &'a mut State, content: T, items: Vec<MenuItem<Message>>) -> Self
+    where
+        T: Into<Element<'a, Message, Renderer>>,
+    {
+        ContextMenu {
+            state,
+            content: content.into(),
+            items,
+            text_size: crate::text_size(crate::TextSize::Body),
+            panel_style: container::StyleSheet::style(&crate::style::Menu),
+            item_style: button::StyleSheet::active(&crate::style::Menu),
+            item_hovered_style: button::StyleSheet::hovered(&crate::style::Menu),
+        }
+    }
+
+    pub fn text_size(mut self, size: u16) -> Self {
+        self.text_size = size;
+        self
+    }
+
+    /// The container style is used for the menu panels, the button style for the items
+    pub fn style(mut self, style: impl container::StyleSheet + button::StyleSheet) -> Self {
+        self.panel_style = container::StyleSheet::style(&style);
+        self.item_style = button::StyleSheet::active(&style);
+        self.item_hovered_style = button::StyleSheet::hovered(&style);
+        self
+    }
+
+    fn level_items(&self, level: usize) -> &[MenuItem<Message>] {
+        let mut items = &self.items[..];
+        for sel in self.state.path.iter().take(level) {
+            match sel.and_then(|i| items.get(i)) {
+                Some(MenuItem::Submenu { items: sub, .. }) => items = sub,
+                _ => return &[],
+            }
+        }
+        items
+    }
+
+    fn layout_panel(
+        &self,
+        renderer: &Renderer,
+        items: &[MenuItem<Message>],
+        origin: Point,
+        viewport: &Rectangle,
+    ) -> Panel {
+        let size = self.text_size as f32;
+        let has_checks = items.iter().any(|i| matches!(i, MenuItem::Check { .. }));
+        let has_subs = items.iter().any(|i| matches!(i, MenuItem::Submenu { .. }));
+        let label_width = items
+            .iter()
+            .filter_map(|i| i.label())
+            .map(|l| {
+                renderer
+                    .measure(l, self.text_size, Default::default(), Size::INFINITY)
+                    .0
+            })
+            .fold(0.0, f32::max);
+        let width = label_width
+            + ITEM_PADDING * 2.0
+            + if has_checks { INDICATOR_WIDTH } else { 0.0 }
+            + if has_subs { INDICATOR_WIDTH } else { 0.0 };
+        let heights = items
+            .iter()
+            .map(|i| match i {
+                MenuItem::Separator => SEPARATOR_HEIGHT,
+                _ => size + ITEM_PADDING * 2.0,
+            })
+            .collect::<Vec<_>>();
+        let height = heights.iter().sum::<f32>() + PANEL_PADDING * 2.0;
+        let width = width + PANEL_PADDING * 2.0;
+
+        let x = origin.x.min(viewport.x + viewport.width - width).max(viewport.x);
+        let y = origin.y.min(viewport.y + viewport.height - height).max(viewport.y);
+        let mut item_y = y + PANEL_PADDING;
+        let item_rects = heights
+            .into_iter()
+            .map(|h| {
+                let r = Rectangle {
+                    x: x + PANEL_PADDING,
+                    y: item_y,
+                    width: width - PANEL_PADDING * 2.0,
+                    height: h,
+                };
+                item_y += h;
+                r
+            })
+            .collect();
+        Panel {
+            bounds: Rectangle { x, y, width, height },
+            items: item_rects,
+        }
+    }
+
+    fn panels(&self, renderer: &Renderer, viewport: &Rectangle) -> Vec<Panel> {
+        let mut result: Vec<Panel> = Vec::new();
+        let mut origin = match self.state.open_at {
+            Some(p) => p,
+            None => return result,
+        };
+        for level in 0..self.state.path.len() {
+            let items = self.level_items(level);
+            if items.is_empty() {
+                break;
+            }
+            let panel = self.layout_panel(renderer, items, origin, viewport);
+            if let Some(sel) = self.state.path[level] {
+                let right = panel.bounds.x + panel.bounds.width;
+                let item_y = panel.items[sel].y - PANEL_PADDING;
+                origin = Point::new(right, item_y);
+                // Flip to the left side when there's no room on the right
+                if let Some(MenuItem::Submenu { items: sub, .. }) = items.get(sel) {
+                    let sub_width = self.layout_panel(renderer, sub, origin, viewport).bounds.width;
+                    if right + sub_width > viewport.x + viewport.width {
+                        origin.x = panel.bounds.x - sub_width;
+                    }
+                }
+            }
+            result.push(panel);
+        }
+        result
+    }
+
+    fn hit(&self, panels: &[Panel], point: Point) -> Option<(usize, Option<usize>)> {
+        panels.iter().enumerate().rev().find_map(|(level, panel)| {
+            if panel.bounds.contains(point) {
+                Some((level, panel.items.iter().position(|r| r.contains(point))))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn first_selectable(items: &[MenuItem<Message>]) -> Option<usize> {
+        items.iter().position(|i| !matches!(i, MenuItem::Separator))
+    }
+
+    fn highlight(&mut self, level: usize, idx: usize) {
+        let is_sub = matches!(self.level_items(level).get(idx), Some(MenuItem::Submenu { .. }));
+        self.state.path.truncate(level + 1);
+        self.state.path[level] = Some(idx);
+        if is_sub {
+            self.state.path.push(None);
+        }
+    }
+
+    fn move_highlight(&mut self, forward: bool) {
+        let level = self.state.path.len() - 1;
+        let selectable = self
+            .level_items(level)
+            .iter()
+            .map(|i| !matches!(i, MenuItem::Separator))
+            .collect::<Vec<_>>();
+        let len = selectable.len();
+        if len == 0 {
+            return;
+        }
+        let mut idx = self.state.path[level].unwrap_or(if forward { len - 1 } else { 0 });
+        for _ in 0..len {
+            idx = if forward {
+                (idx + 1) % len
+            } else {
+                (idx + len - 1) % len
+            };
+            if selectable[idx] {
+                self.state.path[level] = Some(idx);
+                return;
+            }
+        }
+    }
+
+    fn activate(&mut self, level: usize, idx: usize, shell: &mut Shell<'_, Message>) {
+        match self.level_items(level).get(idx) {
+            Some(MenuItem::Action { msg, .. }) | Some(MenuItem::Check { msg, .. }) => {
+                shell.publish(msg.clone());
+                self.state.close();
+            }
+            Some(MenuItem::Submenu { items, .. }) => {
+                let first = Self::first_selectable(items);
+                self.state.path.truncate(level + 1);
+                self.state.path[level] = Some(idx);
+                self.state.path.push(first);
+            }
+            _ => (),
+        }
+    }
+
+    fn on_menu_event(
+        &mut self,
+        event: Event,
+        cursor_position: Point,
+        renderer: &Renderer,
+        shell: &mut Shell<'_, Message>,
+    ) {
+        let mut viewport = self.state.last_viewport.get();
+        if viewport.width == 0.0 {
+            viewport = Rectangle::with_size(Size::INFINITY);
+        }
+        let panels = self.panels(renderer, &viewport);
+        match event {
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                if let Some((level, Some(idx))) = self.hit(&panels, cursor_position) {
+                    if !matches!(self.level_items(level).get(idx), Some(MenuItem::Separator)) {
+                        self.highlight(level, idx);
+                    }
+                }
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(_)) => {
+                self.state.ignore_release = false;
+                if self.hit(&panels, cursor_position).is_none() {
+                    self.state.close();
+                }
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(_)) => {
+                if self.state.ignore_release {
+                    self.state.ignore_release = false;
+                    if let Some(at) = self.state.open_at {
+                        if at.distance(cursor_position) < 4.0 {
+                            return;
+                        }
+                    }
+                }
+                if let Some((level, Some(idx))) = self.hit(&panels, cursor_position) {
+                    self.activate(level, idx, shell);
+                }
+            }
+            Event::Keyboard(keyboard::Event::KeyPressed { key_code, .. }) => {
+                let level = self.state.path.len() - 1;
+                match key_code {
+                    keyboard::KeyCode::Down => self.move_highlight(true),
+                    keyboard::KeyCode::Up => self.move_highlight(false),
+                    keyboard::KeyCode::Right | keyboard::KeyCode::Enter | keyboard::KeyCode::Space => {
+                        if let Some(idx) = self.state.path[level] {
+                            let is_sub = matches!(self.level_items(level).get(idx), Some(MenuItem::Submenu { .. }));
+                            if is_sub || key_code != keyboard::KeyCode::Right {
+                                self.activate(level, idx, shell);
+                            }
+                        }
+                    }
+                    keyboard::KeyCode::Left if level > 0 => {
+                        self.state.path.pop();
+                    }
+                    keyboard::KeyCode::Escape => {
+                        if level > 0 {
+                            self.state.path.pop();
+                        } else {
+                            self.state.close();
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn draw_panel(&self, renderer: &mut Renderer, panel: &Panel, level: usize, style: &renderer::Style) {
+        let items = self.level_items(level);
+        let has_checks = items.iter().any(|i| matches!(i, MenuItem::Check { .. }));
+        let text_color = self.panel_style.text_color.unwrap_or(style.text_color);
+        container::draw_background(renderer, &self.panel_style, panel.bounds);
+        a11y::begin_node(Node::new(Role::Menu, "", panel.bounds));
+        for (i, (item, rect)) in items.iter().zip(panel.items.iter()).enumerate() {
+            let rect = *rect;
+            if let MenuItem::Separator = item {
+                a11y::add_node(Node::new(Role::Separator, "", rect));
+                renderer.fill_quad(
+                    renderer::Quad {
+                        bounds: Rectangle {
+                            x: rect.x + ITEM_PADDING,
+                            y: rect.center_y().floor(),
+                            width: rect.width - ITEM_PADDING * 2.0,
+                            height: 1.0,
+                        },
+                        border_radius: 0.0,
+                        border_width: 0.0,
+                        border_color: Color::TRANSPARENT,
+                    },
+                    Background::Color(Color { a: 0.25, ..text_color }),
+                );
+                continue;
+            }
+            let highlighted = self.state.path.get(level) == Some(&Some(i));
+            let mut node = Node::new(Role::MenuItem, item.label().unwrap_or(""), rect);
+            if let MenuItem::Check { checked, .. } = item {
+                node.role = Role::CheckMenuItem;
+                node.checked = Some(*checked);
+            }
+            node.focused = highlighted;
+            a11y::add_node(node);
+            let item_style = if highlighted {
+                &self.item_hovered_style
+            } else {
+                &self.item_style
+            };
+            if let Some(bg) = item_style.background {
+                renderer.fill_quad(
+                    renderer::Quad {
+                        bounds: rect,
+                        border_radius: item_style.border_radius,
+                        border_width: item_style.border_width,
+                        border_color: item_style.border_color,
+                    },
+                    bg,
+                );
+            }
+            let mut label_x = rect.x + ITEM_PADDING;
+            if has_checks {
+                if let MenuItem::Check { checked, .. } = item {
+                    let sz = self.text_size as f32 / 2.0;
+                    renderer.fill_quad(
+                        renderer::Quad {
+                            bounds: Rectangle {
+                                x: label_x + (INDICATOR_WIDTH - sz) / 2.0 - 2.0,
+                                y: rect.center_y() - sz / 2.0,
+                                width: sz,
+                                height: sz,
+                            },
+                            border_radius: 2.0,
+                            border_width: 1.0,
+                            border_color: item_style.text_color,
+                        },
+                        Background::Color(if *checked {
+                            item_style.text_color
+                        } else {
+                            Color::TRANSPARENT
+                        }),
+                    );
+                }
+                label_x += INDICATOR_WIDTH;
+            }
+            renderer.fill_text(text::Text {
+                content: item.label().unwrap_or(""),
+                bounds: Rectangle {
+                    x: label_x,
+                    y: rect.center_y(),
+                    ..rect
+                },
+                size: self.text_size as f32,
+                color: item_style.text_color,
+                font: Default::default(),
+                horizontal_alignment: alignment::Horizontal::Left,
+                vertical_alignment: alignment::Vertical::Center,
+            });
+            if let MenuItem::Submenu { .. } = item {
+                renderer.fill_text(text::Text {
+                    content: "›",
+                    bounds: Rectangle {
+                        x: rect.x + rect.width - ITEM_PADDING,
+                        y: rect.center_y(),
+                        ..rect
+                    },
+                    size: self.text_size as f32,
+                    color: item_style.text_color,
+                    font: Default::default(),
+                    horizontal_alignment: alignment::Horizontal::Right,
+                    vertical_alignment: alignment::Vertical::Center,
+                });
+            }
+        }
+        a11y::end_node();
+    }
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for ContextMenu<'a, Message, Renderer>
+where
+    Renderer: text::Renderer,
+    Message: Clone,
+{
+    fn width(&self) -> Length {
+        self.content.width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let content = self.content.layout(renderer, &limits);
+        let size = limits.resolve(content.size());
+        layout::Node::with_children(size, vec![content])
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) -> event::Status {
+        if self.state.is_open() {
+            if let Event::Mouse(_) | Event::Keyboard(_) | Event::Touch(_) = event {
+                self.on_menu_event(event, cursor_position, renderer, shell);
+                return event::Status::Captured;
+            }
+        } else if let Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right)) = event {
+            if layout.bounds().contains(cursor_position) && !self.items.is_empty() {
+                self.state.open_at = Some(cursor_position);
+                self.state.path = vec![None];
+                self.state.ignore_release = true;
+                return event::Status::Captured;
+            }
+        }
+
+        self.content.on_event(
+            event,
+            layout.children().next().unwrap(),
+            cursor_position,
+            renderer,
+            clipboard,
+            shell,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        if self.state.is_open() {
+            return mouse::Interaction::Idle;
+        }
+        self.content
+            .mouse_interaction(layout.children().next().unwrap(), cursor_position, viewport, renderer)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) {
+        self.content.draw(
+            renderer,
+            style,
+            layout.children().next().unwrap(),
+            cursor_position,
+            viewport,
+        );
+
+        if !self.state.is_open() {
+            return;
+        }
+        self.state.last_viewport.set(*viewport);
+        let panels = self.panels(renderer, viewport);
+        renderer.with_layer(*viewport, |renderer| {
+            for (level, panel) in panels.iter().enumerate() {
+                super::inputregion::add_rect(panel.bounds.snap());
+                self.draw_panel(renderer, panel, level, style);
+            }
+        });
+    }
+}
+
+impl<'a, Message, Renderer> From<ContextMenu<'a, Message, Renderer>> for Element<'a, Message, Renderer>
+where
+    Renderer: 'a + text::Renderer,
+    Message: 'a + Clone,
+{
+    fn from(x: ContextMenu<'a, Message, Renderer>) -> Element<'a, Message, Renderer> {
+        Element::new(x)
+    }
+}
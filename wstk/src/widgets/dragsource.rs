@@ -0,0 +1,177 @@
+//! Makes the content draggable to other surfaces and apps (or within the same surface)
+
+use crate::dnd::{self, DragData};
+use iced_native::*;
+
+/// How far the pointer has to move while pressed to start dragging
+pub const DRAG_THRESHOLD: f32 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct State {
+    press_at: Option<Point>,
+    drag_id: Option<u64>,
+}
+
+impl State {
+    pub fn is_dragging(&self) -> bool {
+        self.drag_id.is_some()
+    }
+}
+
+pub struct DragSource<'a, Message, Renderer: self::Renderer> {
+    state: &'a mut State,
+    content: Element<'a, Message, Renderer>,
+    data: DragData,
+    on_drag_start: Option<Message>,
+    on_drag_end: Option<Box<dyn Fn(bool) -> Message + 'a>>,
+}
+
+impl<'a, Message, Renderer> DragSource<'a, Message, Renderer>
+where
+    Renderer: self::Renderer,
+{
+    pub fn new<T>(state: &'a mut State, content: T, data: DragData) -> Self
+    where
+        T: Into<Element<'a, Message, Renderer>>,
+    {
+        DragSource {
+            state,
+            content: content.into(),
+            data,
+            on_drag_start: None,
+            on_drag_end: None,
+        }
+    }
+
+    pub fn on_drag_start(mut self, msg: Message) -> Self {
+        self.on_drag_start = Some(msg);
+        self
+    }
+
+    /// Called with true if the data was dropped somewhere that took it,
+    /// false if the drag was cancelled or dropped where nothing accepted it
+    pub fn on_drag_end(mut self, f: impl Fn(bool) -> Message + 'a) -> Self {
+        self.on_drag_end = Some(Box::new(f));
+        self
+    }
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for DragSource<'a, Message, Renderer>
+where
+    Renderer: self::Renderer,
+    Message: Clone,
+{
+    fn width(&self) -> Length {
+        self.content.width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let content = self.content.layout(renderer, &limits);
+        let size = limits.resolve(content.size());
+        layout::Node::with_children(size, vec![content])
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) -> event::Status {
+        if let Some(id) = self.state.drag_id {
+            if let Some(accepted) = dnd::take_drag_end(id) {
+                self.state.drag_id = None;
+                if let Some(ref f) = self.on_drag_end {
+                    shell.publish(f(accepted));
+                }
+            }
+        }
+
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+                if layout.bounds().contains(cursor_position) =>
+            {
+                self.state.press_at = Some(cursor_position);
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                self.state.press_at = None;
+            }
+            Event::Mouse(mouse::Event::CursorMoved { position }) => {
+                if let Some(at) = self.state.press_at {
+                    if at.distance(position) > DRAG_THRESHOLD {
+                        self.state.press_at = None;
+                        self.state.drag_id = Some(dnd::request_drag(self.data.clone()));
+                        if let Some(ref msg) = self.on_drag_start {
+                            shell.publish(msg.clone());
+                        }
+                        // the compositor takes the pointer now, so the release will never come:
+                        // release outside of the content to unpress buttons without clicking them
+                        self.content.on_event(
+                            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)),
+                            layout.children().next().unwrap(),
+                            Point::new(-1.0, -1.0),
+                            renderer,
+                            clipboard,
+                            shell,
+                        );
+                        return event::Status::Captured;
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        self.content.on_event(
+            event,
+            layout.children().next().unwrap(),
+            cursor_position,
+            renderer,
+            clipboard,
+            shell,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content
+            .mouse_interaction(layout.children().next().unwrap(), cursor_position, viewport, renderer)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) {
+        self.content.draw(
+            renderer,
+            style,
+            layout.children().next().unwrap(),
+            cursor_position,
+            viewport,
+        )
+    }
+}
+
+impl<'a, Message, Renderer> From<DragSource<'a, Message, Renderer>> for Element<'a, Message, Renderer>
+where
+    Renderer: 'a + self::Renderer,
+    Message: 'a + Clone,
+{
+    fn from(x: DragSource<'a, Message, Renderer>) -> Element<'a, Message, Renderer> {
+        Element::new(x)
+    }
+}
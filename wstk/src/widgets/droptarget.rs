@@ -0,0 +1,117 @@
+//! Area that accepts drag-and-drop data (files, text, or custom mime types)
+
+use crate::dnd::{self, DropData};
+use iced_native::*;
+
+pub struct DropTarget<'a, Message, Renderer: self::Renderer> {
+    content: Element<'a, Message, Renderer>,
+    mime_types: Vec<String>,
+    on_drop: Box<dyn Fn(DropData) -> Message + 'a>,
+}
+
+impl<'a, Message, Renderer> DropTarget<'a, Message, Renderer>
+where
+    Renderer: self::Renderer,
+{
+    /// Accepts file URIs and plain text by default, see `mime_types`
+    pub fn new<T>(content: T, on_drop: impl Fn(DropData) -> Message + 'a) -> Self
+    where
+        T: Into<Element<'a, Message, Renderer>>,
+    {
+        DropTarget {
+            content: content.into(),
+            mime_types: vec![dnd::MIME_URI_LIST.to_owned(), dnd::MIME_TEXT.to_owned()],
+            on_drop: Box::new(on_drop),
+        }
+    }
+
+    /// Accepted mime types, in order of preference
+    pub fn mime_types(mut self, mime_types: Vec<String>) -> Self {
+        self.mime_types = mime_types;
+        self
+    }
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for DropTarget<'a, Message, Renderer>
+where
+    Renderer: self::Renderer,
+    Message: Clone,
+{
+    fn width(&self) -> Length {
+        self.content.width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let content = self.content.layout(renderer, &limits);
+        let size = limits.resolve(content.size());
+        layout::Node::with_children(size, vec![content])
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) -> event::Status {
+        // nested targets get the first chance to take the drop
+        let status = self.content.on_event(
+            event,
+            layout.children().next().unwrap(),
+            cursor_position,
+            renderer,
+            clipboard,
+            shell,
+        );
+        if let Some(data) = dnd::take_drop(layout.bounds(), &self.mime_types) {
+            shell.publish((self.on_drop)(data));
+            return event::Status::Captured;
+        }
+        status
+    }
+
+    fn mouse_interaction(
+        &self,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content
+            .mouse_interaction(layout.children().next().unwrap(), cursor_position, viewport, renderer)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) {
+        dnd::add_target(layout.bounds(), self.mime_types.clone());
+        self.content.draw(
+            renderer,
+            style,
+            layout.children().next().unwrap(),
+            cursor_position,
+            viewport,
+        )
+    }
+}
+
+impl<'a, Message, Renderer> From<DropTarget<'a, Message, Renderer>> for Element<'a, Message, Renderer>
+where
+    Renderer: 'a + self::Renderer,
+    Message: 'a + Clone,
+{
+    fn from(x: DropTarget<'a, Message, Renderer>) -> Element<'a, Message, Renderer> {
+        Element::new(x)
+    }
+}
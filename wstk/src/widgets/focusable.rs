@@ -0,0 +1,276 @@
+//! Makes the content reachable with the keyboard: Tab/Shift+Tab (handled by the IcedInstance)
+//! and the arrow keys move the focus between Focusable widgets, Enter/Space activate the focused one.
+//! Focusables are identified by their bounds, so they need no state of their own.
+
+use iced_native::*;
+use std::cell::{Cell, RefCell};
+
+thread_local! {
+    static FOCUSED: Cell<Option<Rectangle>> = Cell::new(None);
+    static JUST_FOCUSED: Cell<bool> = Cell::new(false);
+    static REQUESTED: Cell<Option<Rectangle>> = Cell::new(None);
+    static CHAIN: RefCell<Vec<Rectangle>> = RefCell::new(Vec::new());
+    static COLLECTED: RefCell<Option<Vec<Rectangle>>> = RefCell::new(None);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// Sets the focus for the surface that's about to be updated and drawn.
+/// `changed` lets the newly focused widget send its `on_focus` message.
+pub(crate) fn set_focused(focused: Option<Rectangle>, changed: bool) {
+    FOCUSED.with(|f| f.set(focused));
+    JUST_FOCUSED.with(|j| j.set(changed));
+}
+
+/// Whether the Focusable with these bounds has the focus
+pub(crate) fn is_focused(bounds: Rectangle) -> bool {
+    FOCUSED.with(|f| f.get()) == Some(bounds)
+}
+
+/// Focus change asked for by a widget (e.g. arrow key navigation)
+pub(crate) fn take_request() -> Option<Rectangle> {
+    REQUESTED.with(|r| r.take())
+}
+
+/// Starts collecting the focus chain from the Focusable widgets drawn after this call
+pub(crate) fn start_collecting() {
+    COLLECTED.with(|c| c.replace(Some(Vec::new())));
+}
+
+/// Stops collecting and returns the focus chain in traversal order
+pub(crate) fn finish_collecting() -> Vec<Rectangle> {
+    let chain = COLLECTED.with(|c| c.take()).unwrap_or_default();
+    CHAIN.with(|c| c.replace(chain.clone()));
+    chain
+}
+
+/// The closest focusable in the direction, preferring ones that are in line (same row or column)
+pub(crate) fn neighbor(chain: &[Rectangle], from: Rectangle, dir: Direction) -> Option<Rectangle> {
+    let c = from.center();
+    chain
+        .iter()
+        .filter(|b| **b != from)
+        .filter_map(|b| {
+            let o = b.center();
+            let (along, across) = match dir {
+                Direction::Left => (c.x - o.x, o.y - c.y),
+                Direction::Right => (o.x - c.x, o.y - c.y),
+                Direction::Up => (c.y - o.y, o.x - c.x),
+                Direction::Down => (o.y - c.y, o.x - c.x),
+            };
+            if along <= 0.0 {
+                return None;
+            }
+            Some((along + across.abs() * 2.0, *b))
+        })
+        .min_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap())
+        .map(|(_, b)| b)
+}
+
+pub struct Focusable<'a, Message, Renderer: self::Renderer> {
+    content: Element<'a, Message, Renderer>,
+    on_activate: Option<Message>,
+    on_focus: Option<Message>,
+    ring_color: Color,
+    ring_radius: f32,
+    ring_width: f32,
+}
+
+impl<'a, Message, Renderer> Focusable<'a, Message, Renderer>
+where
+    Renderer: self::Renderer,
+{
+    pub fn new<T>(content: T) -> Self
+    where
+        T: Into<Element<'a, Message, Renderer>>,
+    {
+        let theme = crate::theme();
+        Focusable {
+            content: content.into(),
+            on_activate: None,
+            on_focus: None,
+            ring_color: theme.palette.focus,
+            ring_radius: theme.metrics.radius,
+            ring_width: theme.metrics.focus_width,
+        }
+    }
+
+    /// Message for Enter/Space. Without it, the content gets clicked in the middle instead
+    /// (which works for buttons, checkboxes and the like).
+    pub fn on_activate(mut self, msg: Message) -> Self {
+        self.on_activate = Some(msg);
+        self
+    }
+
+    /// Message for when the widget gets focused from the keyboard,
+    /// e.g. to focus/unfocus text inputs that aren't Focusable themselves
+    pub fn on_focus(mut self, msg: Message) -> Self {
+        self.on_focus = Some(msg);
+        self
+    }
+
+    pub fn ring_color(mut self, color: Color) -> Self {
+        self.ring_color = color;
+        self
+    }
+
+    pub fn ring_radius(mut self, radius: f32) -> Self {
+        self.ring_radius = radius;
+        self
+    }
+
+    pub fn ring_width(mut self, width: f32) -> Self {
+        self.ring_width = width;
+        self
+    }
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for Focusable<'a, Message, Renderer>
+where
+    Renderer: self::Renderer,
+    Message: Clone,
+{
+    fn width(&self) -> Length {
+        self.content.width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let content = self.content.layout(renderer, &limits);
+        let size = limits.resolve(content.size());
+        layout::Node::with_children(size, vec![content])
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) -> event::Status {
+        let content_layout = layout.children().next().unwrap();
+        let status = self.content.on_event(
+            event.clone(),
+            content_layout,
+            cursor_position,
+            renderer,
+            clipboard,
+            shell,
+        );
+        let bounds = layout.bounds();
+        if FOCUSED.with(|f| f.get()) != Some(bounds) {
+            return status;
+        }
+        if JUST_FOCUSED.with(|j| j.replace(false)) {
+            if let Some(ref msg) = self.on_focus {
+                shell.publish(msg.clone());
+            }
+        }
+        if status == event::Status::Captured {
+            return status;
+        }
+
+        let key_code = match event {
+            Event::Keyboard(keyboard::Event::KeyPressed { key_code, .. }) => key_code,
+            _ => return status,
+        };
+        let dir = match key_code {
+            keyboard::KeyCode::Enter | keyboard::KeyCode::Space => {
+                if let Some(ref msg) = self.on_activate {
+                    shell.publish(msg.clone());
+                } else {
+                    let center = content_layout.bounds().center();
+                    for ev in [
+                        mouse::Event::ButtonPressed(mouse::Button::Left),
+                        mouse::Event::ButtonReleased(mouse::Button::Left),
+                    ] {
+                        self.content
+                            .on_event(Event::Mouse(ev), content_layout, center, renderer, clipboard, shell);
+                    }
+                }
+                return event::Status::Captured;
+            }
+            keyboard::KeyCode::Left => Direction::Left,
+            keyboard::KeyCode::Right => Direction::Right,
+            keyboard::KeyCode::Up => Direction::Up,
+            keyboard::KeyCode::Down => Direction::Down,
+            _ => return status,
+        };
+        if let Some(next) = CHAIN.with(|c| neighbor(&c.borrow(), bounds, dir)) {
+            REQUESTED.with(|r| r.set(Some(next)));
+        }
+        event::Status::Captured
+    }
+
+    fn mouse_interaction(
+        &self,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content
+            .mouse_interaction(layout.children().next().unwrap(), cursor_position, viewport, renderer)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        COLLECTED.with(|c| {
+            if let Some(ref mut chain) = *c.borrow_mut() {
+                chain.push(bounds);
+            }
+        });
+        self.content.draw(
+            renderer,
+            style,
+            layout.children().next().unwrap(),
+            cursor_position,
+            viewport,
+        );
+        if FOCUSED.with(|f| f.get()) == Some(bounds) {
+            let w = self.ring_width;
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: Rectangle {
+                        x: bounds.x - w,
+                        y: bounds.y - w,
+                        width: bounds.width + w * 2.0,
+                        height: bounds.height + w * 2.0,
+                    },
+                    border_radius: self.ring_radius,
+                    border_width: w,
+                    border_color: self.ring_color,
+                },
+                Background::Color(Color::TRANSPARENT),
+            );
+        }
+    }
+}
+
+impl<'a, Message, Renderer> From<Focusable<'a, Message, Renderer>> for Element<'a, Message, Renderer>
+where
+    Renderer: 'a + self::Renderer,
+    Message: 'a + Clone,
+{
+    fn from(x: Focusable<'a, Message, Renderer>) -> Element<'a, Message, Renderer> {
+        Element::new(x)
+    }
+}
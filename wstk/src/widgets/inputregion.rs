@@ -0,0 +1,123 @@
+//! Marks areas that should receive input, for surfaces using the automatic input region
+
+use iced_native::*;
+use std::cell::RefCell;
+
+thread_local! {
+    static COLLECTED: RefCell<Option<Vec<Rectangle<u32>>>> = RefCell::new(None);
+}
+
+/// Starts collecting regions from the InputRegion widgets drawn after this call
+pub(crate) fn start_collecting() {
+    COLLECTED.with(|c| c.replace(Some(Vec::new())));
+}
+
+/// Stops collecting and returns the regions of the InputRegion widgets drawn since `start_collecting`
+pub(crate) fn finish_collecting() -> Vec<Rectangle<u32>> {
+    COLLECTED.with(|c| c.take()).unwrap_or_default()
+}
+
+pub(crate) fn add_rect(rect: Rectangle<u32>) {
+    COLLECTED.with(|c| {
+        if let Some(ref mut rects) = *c.borrow_mut() {
+            rects.push(rect);
+        }
+    });
+}
+
+/// Makes its content's layout bounds part of the input region
+pub struct InputRegion<'a, Message, Renderer: self::Renderer> {
+    content: Element<'a, Message, Renderer>,
+}
+
+impl<'a, Message, Renderer> InputRegion<'a, Message, Renderer>
+where
+    Renderer: self::Renderer,
+{
+    pub fn new<T>(content: T) -> Self
+    where
+        T: Into<Element<'a, Message, Renderer>>,
+    {
+        InputRegion {
+            content: content.into(),
+        }
+    }
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for InputRegion<'a, Message, Renderer>
+where
+    Renderer: self::Renderer,
+    Message: Clone,
+{
+    fn width(&self) -> Length {
+        self.content.width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let content = self.content.layout(renderer, &limits);
+        let size = limits.resolve(content.size());
+        layout::Node::with_children(size, vec![content])
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) -> event::Status {
+        self.content.on_event(
+            event,
+            layout.children().next().unwrap(),
+            cursor_position,
+            renderer,
+            clipboard,
+            shell,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content
+            .mouse_interaction(layout.children().next().unwrap(), cursor_position, viewport, renderer)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) {
+        add_rect(layout.bounds().snap());
+        self.content.draw(
+            renderer,
+            style,
+            layout.children().next().unwrap(),
+            cursor_position,
+            viewport,
+        )
+    }
+}
+
+impl<'a, Message, Renderer> From<InputRegion<'a, Message, Renderer>> for Element<'a, Message, Renderer>
+where
+    Renderer: 'a + self::Renderer,
+    Message: 'a + Clone,
+{
+    fn from(x: InputRegion<'a, Message, Renderer>) -> Element<'a, Message, Renderer> {
+        Element::new(x)
+    }
+}
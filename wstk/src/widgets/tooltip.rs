@@ -0,0 +1,223 @@
+//! Text hint that shows up next to a widget after hovering it for a bit
+
+use iced_native::{widget::container, *};
+use std::time::{Duration, Instant};
+
+pub const TOOLTIP_DELAY: Duration = Duration::from_millis(600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct State {
+    hover_since: Option<Instant>,
+    dismissed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+pub struct Tooltip<'a, Message, Renderer: text::Renderer> {
+    state: &'a mut State,
+    content: Element<'a, Message, Renderer>,
+    tooltip: widget::Text<Renderer>,
+    position: Position,
+    gap: u16,
+    padding: u16,
+    delay: Duration,
+    style_sheet: Box<dyn container::StyleSheet + 'a>,
+}
+
+impl<'a, Message, Renderer> Tooltip<'a, Message, Renderer>
+where
+    Renderer: text::Renderer,
+{
+    pub fn new<T>(state: &'a mut State, content: T, tooltip: impl Into<String>, position: Position) -> Self
+    where
+        T: Into<Element<'a, Message, Renderer>>,
+    {
+        Tooltip {
+            state,
+            content: content.into(),
+            tooltip: crate::typography::text(tooltip, crate::TextSize::Small),
+            position,
+            gap: 4,
+            padding: 4,
+            delay: TOOLTIP_DELAY,
+            style_sheet: Box::new(crate::style::Tooltip),
+        }
+    }
+
+    pub fn size(mut self, size: u16) -> Self {
+        self.tooltip = self.tooltip.size(size);
+        self
+    }
+
+    /// Distance between the widget and the tooltip
+    pub fn gap(mut self, gap: u16) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    pub fn padding(mut self, padding: u16) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Hover time before showing up, skipped with reduced motion
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn style(mut self, style_sheet: impl Into<Box<dyn container::StyleSheet + 'a>>) -> Self {
+        self.style_sheet = style_sheet.into();
+        self
+    }
+
+    fn is_shown(&self) -> bool {
+        match self.state.hover_since {
+            Some(since) => !self.state.dismissed && since.elapsed() >= crate::motion::duration(self.delay),
+            None => false,
+        }
+    }
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for Tooltip<'a, Message, Renderer>
+where
+    Renderer: text::Renderer,
+    Message: Clone,
+{
+    fn width(&self) -> Length {
+        self.content.width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.height()
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let content = self.content.layout(renderer, &limits);
+        let size = limits.resolve(content.size());
+        layout::Node::with_children(size, vec![content])
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) -> event::Status {
+        if layout.bounds().contains(cursor_position) {
+            if self.state.hover_since.is_none() {
+                let now = Instant::now();
+                self.state.hover_since = Some(now);
+                crate::request_redraw_at(now + crate::motion::duration(self.delay));
+            }
+            if let Event::Mouse(mouse::Event::ButtonPressed(_)) | Event::Touch(_) = event {
+                self.state.dismissed = true;
+            }
+        } else {
+            self.state.hover_since = None;
+            self.state.dismissed = false;
+        }
+
+        self.content.on_event(
+            event,
+            layout.children().next().unwrap(),
+            cursor_position,
+            renderer,
+            clipboard,
+            shell,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content
+            .mouse_interaction(layout.children().next().unwrap(), cursor_position, viewport, renderer)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) {
+        self.content.draw(
+            renderer,
+            style,
+            layout.children().next().unwrap(),
+            cursor_position,
+            viewport,
+        );
+
+        if !self.is_shown() {
+            return;
+        }
+
+        let padding = self.padding as f32;
+        let gap = self.gap as f32;
+        let text_layout = Widget::<Message, Renderer>::layout(
+            &self.tooltip,
+            renderer,
+            &layout::Limits::new(Size::ZERO, viewport.size()).pad(Padding::new(self.padding)),
+        );
+        let text_size = text_layout.size();
+        let (width, height) = (text_size.width + padding * 2.0, text_size.height + padding * 2.0);
+
+        // placed relative to the wrapped widget's bounds, like GetRegion would report them
+        let b = layout.bounds();
+        let (x, y) = match self.position {
+            Position::Top => (b.center_x() - width / 2.0, b.y - gap - height),
+            Position::Bottom => (b.center_x() - width / 2.0, b.y + b.height + gap),
+            Position::Left => (b.x - gap - width, b.center_y() - height / 2.0),
+            Position::Right => (b.x + b.width + gap, b.center_y() - height / 2.0),
+        };
+        let bounds = Rectangle {
+            x: x.max(viewport.x).min(viewport.x + viewport.width - width),
+            y: y.max(viewport.y).min(viewport.y + viewport.height - height),
+            width,
+            height,
+        };
+        super::inputregion::add_rect(bounds.snap());
+
+        let tooltip_style = self.style_sheet.style();
+        let text_style = renderer::Style {
+            text_color: tooltip_style.text_color.unwrap_or(style.text_color),
+        };
+        renderer.with_layer(*viewport, |renderer| {
+            container::draw_background(renderer, &tooltip_style, bounds);
+            Widget::<Message, Renderer>::draw(
+                &self.tooltip,
+                renderer,
+                &text_style,
+                Layout::with_offset(Vector::new(bounds.x + padding, bounds.y + padding), &text_layout),
+                cursor_position,
+                viewport,
+            );
+        });
+    }
+}
+
+impl<'a, Message, Renderer> From<Tooltip<'a, Message, Renderer>> for Element<'a, Message, Renderer>
+where
+    Renderer: 'a + text::Renderer,
+    Message: 'a + Clone,
+{
+    fn from(x: Tooltip<'a, Message, Renderer>) -> Element<'a, Message, Renderer> {
+        Element::new(x)
+    }
+}
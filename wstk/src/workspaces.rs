@@ -0,0 +1,343 @@
+use event_listener;
+use futures::prelude::*;
+use smithay_client_toolkit::{
+    environment::GlobalHandler,
+    reexports::client::{
+        protocol::{wl_output, wl_registry},
+        Attached, DispatchData,
+    },
+};
+
+pub use crate::protocols::ext_workspace::{
+    ext_workspace_group_handle_v1 as workspace_group_handle, ext_workspace_handle_v1 as workspace_handle,
+    ext_workspace_manager_v1 as workspace_manager,
+};
+
+use std::{
+    cell::{Cell, Ref, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
+
+/// Assigned in the order workspaces appear and never reused, like `ToplevelId`
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct WorkspaceId(u64);
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct WorkspaceGroupId(u64);
+
+/// Decoded from the protocol's state bitfield
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct WorkspaceFlags {
+    pub active: bool,
+    pub urgent: bool,
+    /// Not meant to be shown to the user
+    pub hidden: bool,
+}
+
+impl WorkspaceFlags {
+    pub fn from_wire(state: u32) -> WorkspaceFlags {
+        WorkspaceFlags {
+            active: state & workspace_handle::State::Active.to_raw() != 0,
+            urgent: state & workspace_handle::State::Urgent.to_raw() != 0,
+            hidden: state & workspace_handle::State::Hidden.to_raw() != 0,
+        }
+    }
+}
+
+/// Which requests the compositor supports for a workspace, it ignores the others
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct WorkspaceCapabilities {
+    pub activate: bool,
+    pub deactivate: bool,
+    pub remove: bool,
+    pub assign: bool,
+}
+
+impl WorkspaceCapabilities {
+    pub fn from_wire(caps: u32) -> WorkspaceCapabilities {
+        use workspace_handle::WorkspaceCapabilities as C;
+        WorkspaceCapabilities {
+            activate: caps & C::Activate.to_raw() != 0,
+            deactivate: caps & C::Deactivate.to_raw() != 0,
+            remove: caps & C::Remove.to_raw() != 0,
+            assign: caps & C::Assign.to_raw() != 0,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone)]
+pub struct WorkspaceState {
+    pub id: WorkspaceId,
+    pub handle: workspace_handle::ExtWorkspaceHandleV1,
+    /// The compositor's id, stable across sessions if it has one at all (not for display)
+    pub identifier: Option<String>,
+    pub name: String,
+    /// Position in the group's grid, in as many dimensions as the compositor likes (empty if it doesn't arrange them)
+    pub coordinates: Vec<u32>,
+    pub state: WorkspaceFlags,
+    pub capabilities: WorkspaceCapabilities,
+    pub group: Option<WorkspaceGroupId>,
+}
+
+impl WorkspaceState {
+    fn new(id: WorkspaceId, handle: workspace_handle::ExtWorkspaceHandleV1) -> WorkspaceState {
+        WorkspaceState {
+            id,
+            handle,
+            identifier: None,
+            name: "".to_owned(),
+            coordinates: Vec::new(),
+            state: WorkspaceFlags::default(),
+            capabilities: WorkspaceCapabilities::default(),
+            group: None,
+        }
+    }
+}
+
+/// A set of workspaces shared by some outputs (e.g. one group per output, or a single one for all of them)
+#[derive(PartialEq, Clone)]
+pub struct WorkspaceGroupState {
+    pub id: WorkspaceGroupId,
+    pub handle: workspace_group_handle::ExtWorkspaceGroupHandleV1,
+    pub outputs: Vec<wl_output::WlOutput>,
+    pub can_create_workspace: bool,
+}
+
+#[derive(PartialEq, Clone, Default)]
+pub struct WorkspaceStates {
+    pub groups: HashMap<WorkspaceGroupId, WorkspaceGroupState>,
+    pub workspaces: HashMap<WorkspaceId, WorkspaceState>,
+}
+
+impl WorkspaceStates {
+    /// The group shown on the output, None if the compositor doesn't have one there
+    pub fn group_on_output(&self, output: &wl_output::WlOutput) -> Option<&WorkspaceGroupState> {
+        let mut groups = self.groups.values().collect::<Vec<_>>();
+        groups.sort_by_key(|g| g.id);
+        groups.into_iter().find(|g| g.outputs.contains(output))
+    }
+
+    /// The group's workspaces that are meant to be shown, ordered by their coordinates
+    /// (then by age, for compositors that don't send any)
+    pub fn sorted_workspaces(&self, group: WorkspaceGroupId) -> Vec<&WorkspaceState> {
+        let mut sorted = self
+            .workspaces
+            .values()
+            .filter(|w| w.group == Some(group) && !w.state.hidden)
+            .collect::<Vec<_>>();
+        sorted.sort_by(|a, b| a.coordinates.cmp(&b.coordinates).then(a.id.cmp(&b.id)));
+        sorted
+    }
+}
+
+pub struct WorkspaceService {
+    global: RefCell<Option<Attached<workspace_manager::ExtWorkspaceManagerV1>>>,
+    notifier: Rc<event_listener::Event>,
+    states: Rc<RefCell<WorkspaceStates>>,
+    /// Updated by the events as they come, published on the manager's done event
+    pending: Rc<RefCell<WorkspaceStates>>,
+    next_id: Rc<Cell<u64>>,
+}
+
+impl WorkspaceService {
+    pub fn new() -> WorkspaceService {
+        WorkspaceService {
+            global: RefCell::new(None),
+            notifier: Rc::new(event_listener::Event::new()),
+            states: Rc::new(RefCell::new(WorkspaceStates::default())),
+            pending: Rc::new(RefCell::new(WorkspaceStates::default())),
+            next_id: Rc::new(Cell::new(0)),
+        }
+    }
+
+    pub fn state(&self) -> Ref<'_, WorkspaceStates> {
+        self.states.borrow()
+    }
+
+    /// The workspace with the given id, None if it's gone
+    pub fn get(&self, id: WorkspaceId) -> Option<Ref<'_, WorkspaceState>> {
+        Ref::filter_map(self.state(), |s| s.workspaces.get(&id)).ok()
+    }
+
+    pub fn subscribe(&self) -> impl Future<Output = ()> {
+        self.notifier.listen()
+    }
+
+    /// Whether the compositor has workspaces, the methods below do nothing otherwise
+    pub fn is_available(&self) -> bool {
+        self.global.borrow().is_some()
+    }
+
+    /// Whether activating it deactivates the others in its group is up to the compositor
+    pub fn activate(&self, ws: &WorkspaceState) {
+        ws.handle.activate();
+        self.commit();
+    }
+
+    pub fn deactivate(&self, ws: &WorkspaceState) {
+        ws.handle.deactivate();
+        self.commit();
+    }
+
+    pub fn remove(&self, ws: &WorkspaceState) {
+        ws.handle.remove();
+        self.commit();
+    }
+
+    /// The compositor might not use the name, or not create the workspace at all
+    pub fn create_workspace(&self, group: &WorkspaceGroupState, name: &str) {
+        group.handle.create_workspace(name.to_owned());
+        self.commit();
+    }
+
+    fn commit(&self) {
+        if let Some(global) = self.global.borrow().as_ref() {
+            global.commit();
+        }
+    }
+
+    fn next_id(&self) -> u64 {
+        self.next_id.set(self.next_id.get() + 1);
+        self.next_id.get()
+    }
+
+    fn with_workspace(&self, id: WorkspaceId, f: impl FnOnce(&mut WorkspaceState)) {
+        if let Some(ws) = self.pending.borrow_mut().workspaces.get_mut(&id) {
+            f(ws)
+        }
+    }
+
+    fn with_group(&self, id: WorkspaceGroupId, f: impl FnOnce(&mut WorkspaceGroupState)) {
+        if let Some(group) = self.pending.borrow_mut().groups.get_mut(&id) {
+            f(group)
+        }
+    }
+
+    fn on_done(&self) {
+        let changed = *self.pending.borrow() != *self.states.borrow();
+        if changed {
+            *self.states.borrow_mut() = self.pending.borrow().clone();
+            self.notifier.notify(usize::MAX);
+        }
+    }
+}
+
+pub struct WorkspaceServiceRc(pub Rc<WorkspaceService>);
+
+impl GlobalHandler<workspace_manager::ExtWorkspaceManagerV1> for WorkspaceServiceRc {
+    fn created(&mut self, registry: Attached<wl_registry::WlRegistry>, id: u32, version: u32, _: DispatchData) {
+        let main = registry.bind::<workspace_manager::ExtWorkspaceManagerV1>(version, id);
+        let service = self.0.clone();
+        main.quick_assign(move |_, event, _| match event {
+            workspace_manager::Event::WorkspaceGroup { workspace_group } => {
+                let id = WorkspaceGroupId(service.next_id());
+                service.pending.borrow_mut().groups.insert(
+                    id,
+                    WorkspaceGroupState {
+                        id,
+                        handle: workspace_group.detach(),
+                        outputs: Vec::new(),
+                        can_create_workspace: false,
+                    },
+                );
+                let service = service.clone();
+                workspace_group.quick_assign(move |group, event, _| match event {
+                    workspace_group_handle::Event::Capabilities { capabilities } => service.with_group(id, |g| {
+                        g.can_create_workspace =
+                            capabilities & workspace_group_handle::GroupCapabilities::CreateWorkspace.to_raw() != 0
+                    }),
+                    workspace_group_handle::Event::OutputEnter { output } => {
+                        service.with_group(id, |g| g.outputs.push(output))
+                    }
+                    workspace_group_handle::Event::OutputLeave { output } => {
+                        service.with_group(id, |g| g.outputs.retain(|o| *o != output))
+                    }
+                    workspace_group_handle::Event::WorkspaceEnter { workspace } => {
+                        if let Some(&ws) = workspace.as_ref().user_data().get::<WorkspaceId>() {
+                            service.with_workspace(ws, |w| w.group = Some(id));
+                        }
+                    }
+                    workspace_group_handle::Event::WorkspaceLeave { workspace } => {
+                        if let Some(&ws) = workspace.as_ref().user_data().get::<WorkspaceId>() {
+                            service.with_workspace(ws, |w| w.group = w.group.filter(|g| *g != id));
+                        }
+                    }
+                    workspace_group_handle::Event::Removed => {
+                        service.pending.borrow_mut().groups.remove(&id);
+                        group.destroy();
+                    }
+                    x => eprintln!("Unknown workspace group event {:?}", x),
+                });
+            }
+            workspace_manager::Event::Workspace { workspace } => {
+                let id = WorkspaceId(service.next_id());
+                // for finding the id when the handle comes back in a group's events
+                workspace.as_ref().user_data().set(move || id);
+                service
+                    .pending
+                    .borrow_mut()
+                    .workspaces
+                    .insert(id, WorkspaceState::new(id, workspace.detach()));
+                let service = service.clone();
+                workspace.quick_assign(move |workspace, event, _| match event {
+                    workspace_handle::Event::Id { id: identifier } => {
+                        service.with_workspace(id, |w| w.identifier = Some(identifier))
+                    }
+                    workspace_handle::Event::Name { name } => service.with_workspace(id, |w| w.name = name),
+                    workspace_handle::Event::Coordinates { coordinates } => service.with_workspace(id, |w| {
+                        // a list of native endian u32
+                        w.coordinates = coordinates
+                            .chunks_exact(4)
+                            .map(|c| u32::from_ne_bytes(c.try_into().unwrap()))
+                            .collect()
+                    }),
+                    workspace_handle::Event::State { state } => {
+                        service.with_workspace(id, |w| w.state = WorkspaceFlags::from_wire(state))
+                    }
+                    workspace_handle::Event::Capabilities { capabilities } => {
+                        service.with_workspace(id, |w| w.capabilities = WorkspaceCapabilities::from_wire(capabilities))
+                    }
+                    workspace_handle::Event::Removed => {
+                        service.pending.borrow_mut().workspaces.remove(&id);
+                        workspace.destroy();
+                    }
+                    x => eprintln!("Unknown workspace event {:?}", x),
+                });
+            }
+            workspace_manager::Event::Done => service.on_done(),
+            workspace_manager::Event::Finished => {
+                eprintln!("The compositor stopped sending workspaces");
+                *service.global.borrow_mut() = None;
+                *service.pending.borrow_mut() = WorkspaceStates::default();
+                service.on_done();
+            }
+            x => eprintln!("Unknown workspace manager event {:?}", x),
+        });
+        *self.0.global.borrow_mut() = Some((*main).clone());
+    }
+
+    fn get(&self) -> Option<Attached<workspace_manager::ExtWorkspaceManagerV1>> {
+        self.0.global.borrow().clone()
+    }
+}
+
+pub trait HasWorkspaceService {
+    fn workspace_service(&self) -> Rc<WorkspaceService>;
+}
+
+impl HasWorkspaceService for Rc<WorkspaceService> {
+    fn workspace_service(&self) -> Rc<WorkspaceService> {
+        self.clone()
+    }
+}
+
+macro_rules! workspace_handler {
+    ($env:ident, $field:ident) => {
+        impl HasWorkspaceService for $env {
+            fn workspace_service(&self) -> Rc<WorkspaceService> {
+                self.$field.0.clone()
+            }
+        }
+    };
+}